@@ -1,11 +1,24 @@
+use std::fs;
 use std::process::Command;
-use std::path::PathBuf;
-use ini::Ini;
-use crate::profile::load_profiles;
+use std::path::{Path, PathBuf};
+use crate::atomic_file::write_atomic;
+use crate::backend::Backend;
+use crate::backup::Backups;
+use crate::browser::open_url;
+use crate::config_editor::ConfigDocument;
+use crate::container::Container;
+use crate::oidc::{refresh_token, OidcClient};
+use crate::portal::RoleCredentials;
+use crate::profile::{parse_role_arn, Profile, SsoLogin};
+use crate::sso_cache::{cache_dir, find_token, token_path, write_token, CachedToken};
+use chrono::{DateTime, Duration, Utc};
+use dirs::home_dir;
+use sha1::{Digest, Sha1};
 
-fn check_sso_session(profile_name: &str) -> bool {
+fn check_sso_session(profile_name: &str, config_file: &Path) -> bool {
     let output = Command::new("aws")
         .args(["sts", "get-caller-identity", "--profile", profile_name])
+        .env("AWS_CONFIG_FILE", config_file)
         .output();
     
     match output {
@@ -14,58 +27,210 @@ fn check_sso_session(profile_name: &str) -> bool {
     }
 }
 
-pub fn login_to_profile(profile_name: &str, force_reauth: bool, check_session: bool, browser: Option<&str>) -> Result<(), String> {
-    if check_session && !force_reauth && check_sso_session(profile_name) {
-        println!("Profile {} already has a valid session", profile_name);
-        return Ok(());
+/// How to decide whether a profile can skip login.
+pub struct SessionCheck {
+    /// Tokens expiring within this margin count as expired.
+    pub skew: Duration,
+    /// Also call `aws sts get-caller-identity` when the cached token looks valid.
+    pub deep: bool,
+}
+
+/// Checks the profile's cached SSO token, and with a deep check also asks STS.
+pub fn has_valid_session(profile: &Profile, check: &SessionCheck) -> bool {
+    let cached = find_token(&cache_dir(), profile.sso_session.as_deref(), &profile.sso_start_url)
+        .is_some_and(|token| token.is_valid_at(Utc::now(), check.skew));
+    cached
+        && (!check.deep
+            || aws_config_file(profile).is_ok_and(|config_file| check_sso_session(&profile.name, &config_file)))
+}
+
+/// Where config files merged for the AWS CLI are written.
+fn merged_config_dir() -> PathBuf {
+    home_dir()
+        .unwrap()
+        .join(".config")
+        .join("aws-sso-navigator")
+        .join("merged")
+}
+
+/// The file to pass to the AWS CLI as `AWS_CONFIG_FILE` for `profile`. That's
+/// the profile's own file, unless its `[sso-session]` or source profiles are
+/// defined in another file. The AWS CLI only reads one file, so then it's a
+/// copy of the profile's file with those sections added.
+pub fn aws_config_file(profile: &Profile) -> Result<PathBuf, String> {
+    merged_config_file(profile, &merged_config_dir())
+}
+
+fn merged_config_file(profile: &Profile, dir: &Path) -> Result<PathBuf, String> {
+    if profile.referenced_sections.iter().all(|(_, file)| *file == profile.source_file) {
+        return Ok(profile.source_file.clone());
     }
-    
-    println!("Logging into AWS profile: {}", profile_name);
-    let mut cmd = Command::new("aws");
-    cmd.arg("sso")
-        .arg("login")
-        .arg("--profile")
-        .arg(profile_name);
-    
-    if let Some(browser_path) = browser {
-        cmd.env("BROWSER", browser_path);
+
+    let mut config = ConfigDocument::load(&profile.source_file)?;
+    copy_missing_sections(&mut config, &profile.referenced_sections)?;
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{:x}.config", Sha1::digest(profile.name.as_bytes())));
+    write_atomic(&path, config.to_string().as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Appends the sections `config` doesn't have yet, copied from the files they're defined in.
+fn copy_missing_sections(config: &mut ConfigDocument, sections: &[(String, PathBuf)]) -> Result<(), String> {
+    for (section, file) in sections {
+        if config.has_section(section) {
+            continue;
+        }
+        let entries = ConfigDocument::load(file)?.entries(section);
+        let entries: Vec<(&str, &str)> = entries.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect();
+        config.append_section(section, &entries);
     }
-    
-    let status = cmd.status()
+    Ok(())
+}
+
+/// Refreshes the profile's cached SSO token with its refresh token, as the AWS
+/// CLI does, so no browser login is needed. Returns `None` if the cache has
+/// nothing to refresh with.
+pub fn refresh_cached_token(
+    profile: &Profile,
+    cache_dir: &Path,
+    oidc_endpoint: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<Option<CachedToken>, String> {
+    let sso_session = profile.sso_session.as_deref();
+    let Some(token) = find_token(cache_dir, sso_session, &profile.sso_start_url)
+        .filter(|token| token.can_refresh(now))
+    else {
+        return Ok(None);
+    };
+
+    let region = profile.sso_region.as_deref().unwrap_or(&token.region);
+    let refreshed = refresh_token(&OidcClient::new(region, oidc_endpoint), &token)?;
+    write_token(&token_path(cache_dir, sso_session, &profile.sso_start_url), &refreshed)?;
+    Ok(Some(refreshed))
+}
+
+/// Logs in to `profile` through `backend`, unless `session_check` finds a
+/// valid session or the cached token can be refreshed. `profile` must be an
+/// SSO profile, not a chained one.
+pub fn login(backend: &dyn Backend, profile: &Profile, session_check: Option<&SessionCheck>) -> Result<(), String> {
+    if let Some(check) = session_check {
+        if backend.check_session(profile, check) {
+            eprintln!("Profile {} already has a valid session", profile.name);
+            return Ok(());
+        }
+        match backend.refresh(profile) {
+            Ok(Some(token)) => {
+                eprintln!("Refreshed the SSO token for {}, valid until {}", profile.name, token.expires_at);
+                return Ok(());
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Couldn't refresh the SSO token ({}), logging in again", e),
+        }
+    }
+
+    eprintln!("Logging into AWS profile: {}", profile.name);
+    let token = backend.login(profile)?;
+    eprintln!("Logged in, token valid until {}", token.expires_at);
+    Ok(())
+}
+
+/// Gets short-lived credentials for `profile` from the cached SSO token of
+/// `sso_source`, its SSO profile, or from the aws CLI for `credential_source`
+/// profiles. Never logs in: fails if there's no valid token.
+pub fn role_credentials(backend: &dyn Backend, profile: &Profile, sso_source: Option<&Profile>, skew: Duration) -> Result<RoleCredentials, String> {
+    let Some(sso_source) = sso_source else {
+        return export_credentials(profile);
+    };
+    let token = find_token(&cache_dir(), sso_source.sso_session.as_deref(), &sso_source.sso_start_url)
+        .filter(|token| token.is_valid_at(Utc::now(), skew))
+        .ok_or_else(|| format!("No valid SSO session for profile {}, run `aws-sso-navigator auth` first", sso_source.name))?;
+
+    if profile.role_arn.is_some() {
+        // Chained roles need an STS AssumeRole call, left to the aws CLI
+        return export_credentials(profile);
+    }
+
+    let region = profile.sso_region.as_deref().unwrap_or(&token.region);
+    backend.role_credentials(&token.access_token, region, &profile.sso_account_id, &profile.sso_role_name)
+}
+
+/// Resolves credentials with `aws configure export-credentials`, which follows
+/// `source_profile` and `credential_source`.
+fn export_credentials(profile: &Profile) -> Result<RoleCredentials, String> {
+    let output = Command::new("aws")
+        .args(["configure", "export-credentials", "--format", "process", "--profile", &profile.name])
+        .env("AWS_CONFIG_FILE", aws_config_file(profile)?)
+        .output()
         .map_err(|e| format!("Failed to execute aws: {}", e))?;
 
-    if !status.success() {
-        return Err("AWS SSO login failed".to_string());
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to get credentials for {}: {}",
+            profile.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    RoleCredentials::from_process_json(&output.stdout)
+}
+
+/// Copies the profile's section into `[default]` of the AWS config at
+/// `config_path`, along with its `[sso-session]` and source profiles if that
+/// file doesn't have them.
+pub fn set_default_profile(profile: &Profile, config_path: &Path, backups: &Backups) -> Result<(), String> {
+    let source = ConfigDocument::load(&profile.source_file)?;
+    let source_section_name = format!("profile {}", profile.name);
+    if !source.has_section(&source_section_name) {
+        return Err(format!("Profile {} not found in config", profile.name));
+    }
+
+    let mut config = ConfigDocument::load(config_path)?;
+    for (key, value) in source.entries(&source_section_name) {
+        config.set("default", &key, &value);
     }
+    copy_missing_sections(&mut config, &profile.referenced_sections)?;
+    backups.back_up(config_path)?;
+    config.save(config_path)?;
 
+    println!("Set {} as default AWS profile", profile.name);
     Ok(())
 }
 
-pub fn set_default_profile(profile_name: &str, config_path: &PathBuf) -> Result<(), String> {
-    let profiles = load_profiles(config_path);
-    profiles.iter()
-        .find(|p| p.name == profile_name)
-        .ok_or_else(|| format!("Profile {} not found", profile_name))?;
-    
-    let mut ini = Ini::load_from_file(config_path)
-        .map_err(|e| format!("Failed to load AWS config: {}", e))?;
-    
-    let source_section_name = format!("profile {}", profile_name);
-    let source_data: Vec<(String, String)> = ini.section(Some(&source_section_name))
-        .ok_or_else(|| format!("Profile {} not found in config", profile_name))?
-        .iter()
-        .map(|(k, v)| (k.to_string(), v.to_string()))
-        .collect();
-    
-    let mut default_section = ini.with_section(Some("default"));
-    for (key, value) in source_data {
-        default_section.set(&key, &value);
+/// Removes the `[default]` section if it logs in through one of `logins`,
+/// as it does after `set_default_profile`. Returns whether it was removed.
+pub fn clear_default_profile(config_path: &Path, logins: &[SsoLogin], backups: &Backups) -> Result<bool, String> {
+    let mut config = ConfigDocument::load(config_path)?;
+    if !config.has_section("default") {
+        return Ok(false);
+    }
+    let uses_login = logins.iter().any(|login| match &login.sso_session {
+        Some(session) => config.get("default", "sso_session").as_ref() == Some(session),
+        None => config
+            .get("default", "sso_start_url")
+            .is_some_and(|url| normalize_sso_start_url(&url) == normalize_sso_start_url(&login.start_url)),
+    });
+    if !uses_login {
+        return Ok(false);
+    }
+
+    config.remove_section("default");
+    backups.back_up(config_path)?;
+    config.save(config_path)?;
+    Ok(true)
+}
+
+/// Runs `aws sso logout`, which signs out of every session and clears the
+/// AWS CLI's SSO caches.
+pub fn cli_logout() -> Result<(), String> {
+    let status = Command::new("aws")
+        .args(["sso", "logout"])
+        .status()
+        .map_err(|e| format!("Failed to execute aws: {}", e))?;
+
+    if !status.success() {
+        return Err("AWS SSO logout failed".to_string());
     }
-    
-    ini.write_to_file(config_path)
-        .map_err(|e| format!("Failed to write AWS config: {}", e))?;
-    
-    println!("Set {} as default AWS profile", profile_name);
     Ok(())
 }
 
@@ -73,31 +238,124 @@ fn normalize_sso_start_url(url: &str) -> &str {
     url.trim_end_matches('/').trim_end_matches('#').trim_end_matches('/')
 }
 
-pub fn open_console(sso_start_url: &str, sso_account_id: &str, sso_role_name: &str, browser: Option<&str>) -> Result<(), String> {
-    let base_url = normalize_sso_start_url(sso_start_url);
-    let url = format!("{}/#/console?account_id={}&role_name={}", base_url, sso_account_id, sso_role_name);
-    
-    let mut cmd = if let Some(browser_path) = browser {
-        Command::new(browser_path)
-    } else {
-        Command::new("open")
+pub fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn switch_role_url(profile_name: &str, role_arn: &str) -> Option<String> {
+    let (account_id, role_name) = parse_role_arn(role_arn)?;
+    Some(format!(
+        "https://signin.aws.amazon.com/switchrole?roleName={}&account={}&displayName={}",
+        url_encode(&role_name),
+        account_id,
+        url_encode(profile_name)
+    ))
+}
+
+/// Where in the console to land. Empty fields fall back to the console home
+/// page in the profile's region.
+#[derive(Debug, Default)]
+pub struct ConsoleTarget {
+    /// Service path, e.g. `s3` or `cloudwatch`.
+    pub service: Option<String>,
+    pub region: Option<String>,
+    /// Full console URL, or a path such as `/s3/buckets`.
+    pub destination: Option<String>,
+}
+
+impl ConsoleTarget {
+    /// The console URL to land on, if there's anything beyond the default
+    /// home page to go to.
+    fn destination_url(&self, profile: &Profile) -> Option<String> {
+        let region = self.region.as_ref().or(profile.region.as_ref());
+        let host = match region {
+            Some(region) => format!("https://{region}.console.aws.amazon.com"),
+            None => "https://console.aws.amazon.com".to_string(),
+        };
+
+        if let Some(destination) = &self.destination {
+            if destination.starts_with("https://") {
+                return Some(destination.clone());
+            }
+            let path = destination.trim_start_matches('/');
+            return Some(format!("{}/{}", host, path));
+        }
+
+        let service = self.service.as_deref().unwrap_or("console");
+        match region {
+            Some(region) => Some(format!("{}/{}/home?region={}", host, service, region)),
+            None if self.service.is_some() => Some(format!("{}/{}/home", host, service)),
+            None => None,
+        }
+    }
+}
+
+/// Builds the console URL for a profile. Chained profiles sign in through their
+/// SSO profile and then switch to the assumed role.
+pub fn console_url(profile: &Profile, target: &ConsoleTarget) -> Result<String, String> {
+    let destination = target.destination_url(profile);
+    let switch_role = match &profile.role_arn {
+        Some(role_arn) => {
+            let mut url = switch_role_url(&profile.name, role_arn)
+                .ok_or_else(|| format!("Invalid role_arn: {}", role_arn))?;
+            // The switch role page goes on to redirect_uri once the role is assumed
+            if (target.service.is_some() || target.destination.is_some())
+                && let Some(destination) = &destination
+            {
+                url.push_str(&format!("&redirect_uri={}", url_encode(destination)));
+            }
+            Some(url)
+        }
+        None => None,
     };
-    
-    cmd.arg(&url);
-    
-    let status = cmd.status()
-        .map_err(|e| format!("Failed to open browser: {}", e))?;
 
-    if !status.success() {
-        return Err("Failed to open console".to_string());
+    if profile.sso_start_url.is_empty() {
+        // credential_source profiles have no SSO portal to go through
+        return switch_role.ok_or_else(|| format!("Profile {} has no SSO start URL", profile.name));
+    }
+
+    let base_url = normalize_sso_start_url(&profile.sso_start_url);
+    let mut url = format!(
+        "{}/#/console?account_id={}&role_name={}",
+        base_url, profile.sso_account_id, profile.sso_role_name
+    );
+    // Land on the requested page, or the switch role page for chained profiles
+    if let Some(destination) = switch_role.or(destination) {
+        url.push_str(&format!("&destination={}", url_encode(&destination)));
     }
+    Ok(url)
+}
 
+/// Opens the console, in a Firefox container when `container` is set.
+pub fn open_console(profile: &Profile, target: &ConsoleTarget, container: Option<&Container>, browser: Option<&str>) -> Result<(), String> {
+    let url = console_url(profile, target)?;
     println!("Opening AWS console: {}", url);
+    match container {
+        Some(container) => open_url(&container.wrap_url(&url), Some(container_browser(browser))),
+        None => open_url(&url, browser),
+    }
     Ok(())
 }
+
+/// Only Firefox, with the container extensions, handles `ext+container:` URLs,
+/// so they go to `firefox` rather than the platform's opener unless `browser`
+/// is set, in which case it has to be Firefox too.
+fn container_browser(browser: Option<&str>) -> &str {
+    browser.unwrap_or("firefox {url}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{mock_server, FakeBackend};
 
     #[test]
     fn test_normalize_sso_start_url() {
@@ -106,4 +364,326 @@ mod tests {
         assert_eq!(normalize_sso_start_url("https://account-3.awsapps.com/start/#"), "https://account-3.awsapps.com/start");
         assert_eq!(normalize_sso_start_url("https://account-4.awsapps.com/start/#/"), "https://account-4.awsapps.com/start");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_container_browser() {
+        assert_eq!(container_browser(None), "firefox {url}");
+        assert_eq!(container_browser(Some("firefox-developer-edition")), "firefox-developer-edition");
+    }
+
+    fn test_profile() -> Profile {
+        Profile {
+            name: "client-dev-admin".to_string(),
+            path: vec!["client".to_string(), "dev".to_string(), "admin".to_string()],
+            sso_account_id: "123456789012".to_string(),
+            sso_role_name: "AdministratorAccess".to_string(),
+            sso_start_url: "https://example.awsapps.com/start/".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_console_url_sso_profile() {
+        assert_eq!(
+            console_url(&test_profile(), &ConsoleTarget::default()).unwrap(),
+            "https://example.awsapps.com/start/#/console?account_id=123456789012&role_name=AdministratorAccess"
+        );
+    }
+
+    #[test]
+    fn test_console_url_region() {
+        let profile = Profile {
+            region: Some("eu-west-1".to_string()),
+            ..test_profile()
+        };
+        assert_eq!(
+            console_url(&profile, &ConsoleTarget::default()).unwrap(),
+            "https://example.awsapps.com/start/#/console?account_id=123456789012&role_name=AdministratorAccess\
+             &destination=https%3A%2F%2Feu-west-1.console.aws.amazon.com%2Fconsole%2Fhome%3Fregion%3Deu-west-1"
+        );
+    }
+
+    #[test]
+    fn test_console_url_chained_profile() {
+        let profile = Profile {
+            name: "client-prod-deploy".to_string(),
+            role_arn: Some("arn:aws:iam::987654321098:role/Deploy".to_string()),
+            chain: vec!["client-dev-admin".to_string()],
+            ..test_profile()
+        };
+        assert_eq!(
+            console_url(&profile, &ConsoleTarget::default()).unwrap(),
+            "https://example.awsapps.com/start/#/console?account_id=123456789012&role_name=AdministratorAccess\
+             &destination=https%3A%2F%2Fsignin.aws.amazon.com%2Fswitchrole%3FroleName%3DDeploy%26account%3D987654321098%26displayName%3Dclient-prod-deploy"
+        );
+
+        let profile = Profile {
+            sso_start_url: String::new(),
+            chain: Vec::new(),
+            credential_source: Some("Ec2InstanceMetadata".to_string()),
+            ..profile
+        };
+        assert_eq!(
+            console_url(&profile, &ConsoleTarget::default()).unwrap(),
+            "https://signin.aws.amazon.com/switchrole?roleName=Deploy&account=987654321098&displayName=client-prod-deploy"
+        );
+    }
+
+    #[test]
+    fn test_console_url_service() {
+        let profile = Profile {
+            region: Some("eu-west-1".to_string()),
+            ..test_profile()
+        };
+        let target = ConsoleTarget {
+            service: Some("s3".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            console_url(&profile, &target).unwrap(),
+            "https://example.awsapps.com/start/#/console?account_id=123456789012&role_name=AdministratorAccess\
+             &destination=https%3A%2F%2Feu-west-1.console.aws.amazon.com%2Fs3%2Fhome%3Fregion%3Deu-west-1"
+        );
+
+        // An explicit region wins over the profile's
+        let target = ConsoleTarget {
+            service: Some("cloudwatch".to_string()),
+            region: Some("us-east-2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            target.destination_url(&profile).as_deref(),
+            Some("https://us-east-2.console.aws.amazon.com/cloudwatch/home?region=us-east-2")
+        );
+
+        let target = ConsoleTarget {
+            service: Some("iam".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            target.destination_url(&test_profile()).as_deref(),
+            Some("https://console.aws.amazon.com/iam/home")
+        );
+    }
+
+    #[test]
+    fn test_console_url_destination() {
+        let profile = Profile {
+            region: Some("eu-west-1".to_string()),
+            ..test_profile()
+        };
+        let target = ConsoleTarget {
+            destination: Some("/lambda/home?region=eu-west-1#/functions".to_string()),
+            service: Some("ignored".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            target.destination_url(&profile).as_deref(),
+            Some("https://eu-west-1.console.aws.amazon.com/lambda/home?region=eu-west-1#/functions")
+        );
+
+        let target = ConsoleTarget {
+            destination: Some("https://s3.console.aws.amazon.com/s3/buckets".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            target.destination_url(&profile).as_deref(),
+            Some("https://s3.console.aws.amazon.com/s3/buckets")
+        );
+
+        // Chained profiles pass the destination on through the switch role page
+        let profile = Profile {
+            name: "client-prod-deploy".to_string(),
+            role_arn: Some("arn:aws:iam::987654321098:role/Deploy".to_string()),
+            sso_start_url: String::new(),
+            credential_source: Some("Ec2InstanceMetadata".to_string()),
+            ..profile
+        };
+        assert_eq!(
+            console_url(&profile, &target).unwrap(),
+            "https://signin.aws.amazon.com/switchrole?roleName=Deploy&account=987654321098&displayName=client-prod-deploy\
+             &redirect_uri=https%3A%2F%2Fs3.console.aws.amazon.com%2Fs3%2Fbuckets"
+        );
+    }
+
+    #[test]
+    fn test_set_default_profile_keeps_formatting() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let contents = "\
+# Work accounts
+[default]
+region=us-east-1
+output = text
+
+[profile client-dev-admin]
+sso_session = example
+sso_account_id = 123456789012
+region = eu-west-1
+";
+        std::fs::write(&config_path, contents).unwrap();
+        let profile = Profile {
+            source_file: config_path.clone(),
+            ..test_profile()
+        };
+
+        let backups = Backups::new(temp_dir.path().join("backups"), 5);
+        set_default_profile(&profile, &config_path, &backups).unwrap();
+        assert_eq!(std::fs::read_to_string(&backups.list()[0].path).unwrap(), contents);
+        assert_eq!(
+            std::fs::read_to_string(&config_path).unwrap(),
+            contents.replace(
+                "region=us-east-1\noutput = text\n",
+                "region=eu-west-1\noutput = text\nsso_session = example\nsso_account_id = 123456789012\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_sections_from_other_files_are_merged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let personal = temp_dir.path().join("personal");
+        let shared = temp_dir.path().join("shared");
+        std::fs::write(&personal, "[profile me-dev-admin]\nsso_session = team\nsso_account_id = 111111111111\n").unwrap();
+        std::fs::write(&shared, "# Shared\n[sso-session team]\nsso_start_url = https://team.example.com\nsso_region = us-east-1\n").unwrap();
+        let profile = Profile {
+            name: "me-dev-admin".to_string(),
+            sso_session: Some("team".to_string()),
+            source_file: personal.clone(),
+            referenced_sections: vec![("sso-session team".to_string(), shared.clone())],
+            ..test_profile()
+        };
+
+        let merged_dir = temp_dir.path().join("merged");
+        let merged = merged_config_file(&profile, &merged_dir).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&merged).unwrap(),
+            "[profile me-dev-admin]\nsso_session = team\nsso_account_id = 111111111111\n\n\
+             [sso-session team]\nsso_start_url = https://team.example.com\nsso_region = us-east-1\n"
+        );
+        // The profile's own file is used as is when it has everything
+        let local = Profile {
+            referenced_sections: vec![("sso-session team".to_string(), personal.clone())],
+            ..profile.clone()
+        };
+        assert_eq!(merged_config_file(&local, &merged_dir).unwrap(), personal);
+
+        // The default profile gets the sso-session it needs too
+        let config_path = temp_dir.path().join("config");
+        std::fs::write(&config_path, "[default]\nregion = eu-west-1\n").unwrap();
+        let backups = Backups::new(temp_dir.path().join("backups"), 5);
+        set_default_profile(&profile, &config_path, &backups).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&config_path).unwrap(),
+            "[default]\nregion = eu-west-1\nsso_session = team\nsso_account_id = 111111111111\n\n\
+             [sso-session team]\nsso_start_url = https://team.example.com\nsso_region = us-east-1\n"
+        );
+    }
+
+    #[test]
+    fn test_clear_default_profile() {
+        let config = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            config.path(),
+            "[default]\nsso_session = example\nsso_account_id = 123456789012\n\n\
+             [profile client-dev-admin]\nsso_session = example\n",
+        )
+        .unwrap();
+        let login = SsoLogin {
+            name: "other".to_string(),
+            sso_session: Some("other".to_string()),
+            start_url: "https://other.example.com".to_string(),
+            region: None,
+            profiles: Vec::new(),
+        };
+
+        let backup_dir = tempfile::tempdir().unwrap();
+        let backups = Backups::new(backup_dir.path().to_path_buf(), 5);
+        assert!(!clear_default_profile(config.path(), std::slice::from_ref(&login), &backups).unwrap());
+        assert!(backups.list().is_empty());
+        let login = SsoLogin {
+            name: "example".to_string(),
+            sso_session: Some("example".to_string()),
+            ..login
+        };
+        assert!(clear_default_profile(config.path(), &[login], &backups).unwrap());
+        assert_eq!(backups.list().len(), 1);
+
+        assert_eq!(
+            std::fs::read_to_string(config.path()).unwrap(),
+            "[profile client-dev-admin]\nsso_session = example\n"
+        );
+    }
+
+    #[test]
+    fn test_refresh_cached_token() {
+        let (base_url, server) = mock_server(vec![(
+            200,
+            r#"{"accessToken":"new-access","tokenType":"Bearer","expiresIn":28800,"refreshToken":"rotated"}"#,
+        )]);
+        let temp_dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let profile = Profile {
+            sso_session: Some("my-sso".to_string()),
+            sso_region: Some("us-east-1".to_string()),
+            ..test_profile()
+        };
+        let path = token_path(temp_dir.path(), Some("my-sso"), &profile.sso_start_url);
+        let expired = CachedToken {
+            start_url: profile.sso_start_url.clone(),
+            region: "us-east-1".to_string(),
+            access_token: "old-access".to_string(),
+            expires_at: "2020-01-01T00:00:00Z".to_string(),
+            client_id: Some("cid".to_string()),
+            client_secret: Some("secret".to_string()),
+            registration_expires_at: None,
+            refresh_token: Some("refresh".to_string()),
+        };
+
+        // Without an unexpired client registration there's nothing to refresh with
+        write_token(&path, &expired).unwrap();
+        assert_eq!(refresh_cached_token(&profile, temp_dir.path(), Some(&base_url), now), Ok(None));
+
+        let registered = CachedToken {
+            registration_expires_at: Some("2099-01-01T00:00:00Z".to_string()),
+            ..expired
+        };
+        write_token(&path, &registered).unwrap();
+        let refreshed = refresh_cached_token(&profile, temp_dir.path(), Some(&base_url), now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(refreshed.access_token, "new-access");
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("rotated"));
+        assert_eq!(find_token(temp_dir.path(), Some("my-sso"), &profile.sso_start_url), Some(refreshed));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].body.contains("\"refreshToken\":\"refresh\""));
+    }
+
+    #[test]
+    fn test_login_checks_and_refreshes_before_logging_in() {
+        let profile = test_profile();
+        let check = SessionCheck { skew: Duration::zero(), deep: false };
+
+        let backend = FakeBackend { valid_session: true, ..Default::default() };
+        login(&backend, &profile, Some(&check)).unwrap();
+        assert_eq!(backend.calls(), vec!["check_session client-dev-admin"]);
+
+        let backend = FakeBackend { refreshable: true, ..Default::default() };
+        login(&backend, &profile, Some(&check)).unwrap();
+        assert_eq!(backend.calls(), vec!["check_session client-dev-admin", "refresh client-dev-admin"]);
+
+        let backend = FakeBackend::default();
+        login(&backend, &profile, Some(&check)).unwrap();
+        assert_eq!(
+            backend.calls(),
+            vec!["check_session client-dev-admin", "refresh client-dev-admin", "login client-dev-admin"]
+        );
+
+        // Without a session check, e.g. with --force-reauth, it always logs in
+        let backend = FakeBackend { valid_session: true, refreshable: true, ..Default::default() };
+        login(&backend, &profile, None).unwrap();
+        assert_eq!(backend.calls(), vec!["login client-dev-admin"]);
+    }
+}
@@ -0,0 +1,207 @@
+use std::process::Command;
+use chrono::Utc;
+use crate::aws::{aws_config_file, has_valid_session, refresh_cached_token, SessionCheck};
+use crate::browser::{browser_env, open_url};
+use crate::oidc::{device_login, OidcClient, SsoTarget};
+use crate::portal::{parse_role_credentials, Account, AccountList, PortalClient, Role, RoleCredentials, RoleList};
+use crate::profile::Profile;
+use crate::sso_cache::{cache_dir, find_token, token_path, write_token, CachedToken};
+
+/// The calls to AWS behind login, session checks, import and credentials, so
+/// those flows can run against a fake.
+pub trait Backend {
+    /// Logs in to the profile's SSO session, prompting the user, and returns
+    /// the token it cached.
+    fn login(&self, profile: &Profile) -> Result<CachedToken, String>;
+
+    /// Whether the profile already has a session that doesn't need a login.
+    fn check_session(&self, profile: &Profile, check: &SessionCheck) -> bool;
+
+    /// Refreshes the profile's expired token without prompting. `None` if the
+    /// cache has nothing to refresh with.
+    fn refresh(&self, profile: &Profile) -> Result<Option<CachedToken>, String>;
+
+    fn list_accounts(&self, access_token: &str, region: &str) -> Result<Vec<Account>, String>;
+
+    fn list_roles(&self, access_token: &str, region: &str, account_id: &str) -> Result<Vec<Role>, String>;
+
+    fn role_credentials(
+        &self,
+        access_token: &str,
+        region: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<RoleCredentials, String>;
+}
+
+/// Goes through the `aws` CLI. The profile's own config file is passed on,
+/// since it may not be the AWS CLI's config, see `aws_config_file`.
+#[derive(Default)]
+pub struct CliBackend {
+    pub browser: Option<String>,
+    pub oidc_endpoint: Option<String>,
+}
+
+impl CliBackend {
+    /// Runs `aws sso` with `args` and returns its JSON output.
+    fn sso_output(&self, args: &[&str], action: &str) -> Result<Vec<u8>, String> {
+        let output = Command::new("aws")
+            .arg("sso")
+            .args(args)
+            .output()
+            .map_err(|e| format!("Failed to execute aws: {}", e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to {}: {}",
+                action,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Backend for CliBackend {
+    fn login(&self, profile: &Profile) -> Result<CachedToken, String> {
+        let mut cmd = Command::new("aws");
+        cmd.args(["sso", "login"]).env("AWS_CONFIG_FILE", aws_config_file(profile)?);
+        match &profile.sso_session {
+            Some(sso_session) => cmd.args(["--sso-session", sso_session]),
+            None => cmd.args(["--profile", &profile.name]),
+        };
+        if let Some(browser_command) = browser_env(self.browser.as_deref()) {
+            cmd.env("BROWSER", browser_command);
+        }
+
+        // Its prompts go to stderr with ours, keeping stdout for `exec`'s command
+        let status = cmd.stdout(std::io::stderr()).status()
+            .map_err(|e| format!("Failed to execute aws: {}", e))?;
+        if !status.success() {
+            return Err("AWS SSO login failed".to_string());
+        }
+
+        find_token(&cache_dir(), profile.sso_session.as_deref(), &profile.sso_start_url)
+            .ok_or_else(|| format!("aws sso login didn't cache a token for {}", profile.name))
+    }
+
+    fn check_session(&self, profile: &Profile, check: &SessionCheck) -> bool {
+        has_valid_session(profile, check)
+    }
+
+    fn refresh(&self, profile: &Profile) -> Result<Option<CachedToken>, String> {
+        refresh_cached_token(profile, &cache_dir(), self.oidc_endpoint.as_deref(), Utc::now())
+    }
+
+    fn list_accounts(&self, access_token: &str, region: &str) -> Result<Vec<Account>, String> {
+        let output = self.sso_output(
+            &["list-accounts", "--region", region, "--access-token", access_token],
+            "list accounts",
+        )?;
+        let accounts: AccountList = serde_json::from_slice(&output)
+            .map_err(|e| format!("Failed to parse accounts: {}", e))?;
+        Ok(accounts.account_list)
+    }
+
+    fn list_roles(&self, access_token: &str, region: &str, account_id: &str) -> Result<Vec<Role>, String> {
+        let output = self.sso_output(
+            &["list-account-roles", "--region", region, "--access-token", access_token, "--account-id", account_id],
+            "list roles",
+        )?;
+        let roles: RoleList = serde_json::from_slice(&output)
+            .map_err(|e| format!("Failed to parse roles: {}", e))?;
+        Ok(roles.role_list)
+    }
+
+    fn role_credentials(
+        &self,
+        access_token: &str,
+        region: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<RoleCredentials, String> {
+        let output = self.sso_output(
+            &[
+                "get-role-credentials",
+                "--role-name", role_name,
+                "--account-id", account_id,
+                "--access-token", access_token,
+                "--region", region,
+            ],
+            "get role credentials",
+        )?;
+        parse_role_credentials(&output)
+    }
+}
+
+/// Calls the SSO OIDC and portal APIs directly, so the `aws` CLI doesn't need
+/// to be installed.
+#[derive(Default)]
+pub struct NativeBackend {
+    pub browser: Option<String>,
+    pub oidc_endpoint: Option<String>,
+    pub portal_endpoint: Option<String>,
+}
+
+impl NativeBackend {
+    fn portal(&self, region: &str) -> PortalClient {
+        PortalClient::new(region, self.portal_endpoint.as_deref())
+    }
+}
+
+impl Backend for NativeBackend {
+    /// Runs the SSO OIDC device flow and writes the token to the AWS CLI's cache.
+    fn login(&self, profile: &Profile) -> Result<CachedToken, String> {
+        if profile.sso_start_url.is_empty() {
+            return Err(format!("Profile {} has no SSO start URL", profile.name));
+        }
+        let region = profile.sso_region.as_deref()
+            .ok_or_else(|| format!("Profile {} has no sso_region", profile.name))?;
+        let target = SsoTarget {
+            sso_session: profile.sso_session.as_deref(),
+            start_url: &profile.sso_start_url,
+            region,
+        };
+
+        let client = OidcClient::new(region, self.oidc_endpoint.as_deref());
+        let token = device_login(&client, &target, |authorization| {
+            let url = authorization
+                .verification_uri_complete
+                .as_deref()
+                .unwrap_or(&authorization.verification_uri);
+            eprintln!("Approve the login in your browser: {}", url);
+            eprintln!("Code: {}", authorization.user_code);
+            open_url(url, self.browser.as_deref());
+        })?;
+
+        let path = token_path(&cache_dir(), target.sso_session, target.start_url);
+        write_token(&path, &token)?;
+        Ok(token)
+    }
+
+    fn check_session(&self, profile: &Profile, check: &SessionCheck) -> bool {
+        has_valid_session(profile, check)
+    }
+
+    fn refresh(&self, profile: &Profile) -> Result<Option<CachedToken>, String> {
+        refresh_cached_token(profile, &cache_dir(), self.oidc_endpoint.as_deref(), Utc::now())
+    }
+
+    fn list_accounts(&self, access_token: &str, region: &str) -> Result<Vec<Account>, String> {
+        self.portal(region).list_accounts(access_token)
+    }
+
+    fn list_roles(&self, access_token: &str, region: &str, account_id: &str) -> Result<Vec<Role>, String> {
+        self.portal(region).list_roles(access_token, account_id)
+    }
+
+    fn role_credentials(
+        &self,
+        access_token: &str,
+        region: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<RoleCredentials, String> {
+        self.portal(region).role_credentials(access_token, account_id, role_name)
+    }
+}
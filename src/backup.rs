@@ -0,0 +1,273 @@
+use crate::atomic_file::write_atomic;
+use crate::config::Settings;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use dirs::home_dir;
+use sha1::{Digest, Sha1};
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Backup ids are their UTC creation time, so they sort oldest first.
+const ID_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+const EXTENSION: &str = ".config";
+/// In each file's backup directory, holds the path of the file backed up.
+const SOURCE_FILE: &str = "source";
+const DEFAULT_LIMIT: usize = 20;
+
+pub fn backup_dir() -> PathBuf {
+    home_dir()
+        .unwrap()
+        .join(".config")
+        .join("aws-sso-navigator")
+        .join("backups")
+}
+
+/// A copy of the AWS config taken before a change.
+#[derive(Debug, PartialEq)]
+pub struct Backup {
+    pub id: String,
+    pub path: PathBuf,
+    /// The config file this is a copy of, where it's restored to.
+    pub source: PathBuf,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Backup {
+    /// Number of `[profile ...]` sections, to spot a backup from before profiles went missing.
+    pub fn profile_count(&self) -> usize {
+        fs::read_to_string(&self.path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| line.trim_start().starts_with("[profile "))
+            .count()
+    }
+}
+
+/// Where backups of the AWS config go and how many are kept. Each config file
+/// gets its own subdirectory, so backups of different files are never mixed up.
+pub struct Backups {
+    dir: PathBuf,
+    limit: usize,
+}
+
+impl Backups {
+    pub fn new(dir: PathBuf, limit: usize) -> Self {
+        Self { dir, limit }
+    }
+
+    /// Keeps `max_backups` backups per file, 20 by default. Zero turns backups off.
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self::new(backup_dir(), settings.max_backups.unwrap_or(DEFAULT_LIMIT))
+    }
+
+    /// Copies `config_path` before it's modified and drops its oldest backups
+    /// beyond the limit. Nothing is copied when the file doesn't exist yet or
+    /// matches its newest backup.
+    pub fn back_up(&self, config_path: &Path) -> Result<Option<Backup>, String> {
+        if self.limit == 0 {
+            return Ok(None);
+        }
+        let Ok(contents) = fs::read(config_path) else {
+            return Ok(None);
+        };
+        let source = source_path(config_path);
+        let all = self.list();
+        let backups: Vec<&Backup> = all.iter().filter(|backup| backup.source == source).collect();
+        if backups
+            .first()
+            .is_some_and(|newest| fs::read(&newest.path).is_ok_and(|newest| newest == contents))
+        {
+            return Ok(None);
+        }
+
+        let dir = self.dir.join(format!("{:x}", Sha1::digest(source.to_string_lossy().as_bytes())));
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+        write_atomic(&dir.join(SOURCE_FILE), source.to_string_lossy().as_bytes())
+            .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+        // Ids are unique across files, so `restore` needs nothing else
+        let mut created_at = Utc::now();
+        while all.iter().any(|backup| backup.id == created_at.format(ID_FORMAT).to_string()) {
+            created_at += Duration::milliseconds(1);
+        }
+        let id = created_at.format(ID_FORMAT).to_string();
+        let path = dir.join(format!("{}{}", id, EXTENSION));
+        write_atomic(&path, &contents)
+            .map_err(|e| format!("Failed to back up {}: {}", config_path.display(), e))?;
+
+        for old in backups.iter().skip(self.limit - 1) {
+            fs::remove_file(&old.path)
+                .map_err(|e| format!("Failed to remove backup {}: {}", old.id, e))?;
+        }
+        Ok(Some(Backup {
+            id,
+            path,
+            source,
+            created_at,
+        }))
+    }
+
+    /// Backups of all files, newest first.
+    pub fn list(&self) -> Vec<Backup> {
+        let Ok(dirs) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut backups: Vec<Backup> = dirs
+            .filter_map(|dir| dir.ok())
+            .filter_map(|dir| {
+                let source = fs::read_to_string(dir.path().join(SOURCE_FILE)).ok()?;
+                Some((PathBuf::from(source), fs::read_dir(dir.path()).ok()?))
+            })
+            .flat_map(|(source, entries)| {
+                entries.filter_map(|entry| entry.ok()).filter_map(move |entry| {
+                    let file_name = entry.file_name().to_string_lossy().into_owned();
+                    let id = file_name.strip_suffix(EXTENSION)?;
+                    let created_at = NaiveDateTime::parse_from_str(id, ID_FORMAT).ok()?.and_utc();
+                    Some(Backup {
+                        id: id.to_string(),
+                        path: entry.path(),
+                        source: source.clone(),
+                        created_at,
+                    })
+                })
+            })
+            .collect();
+        backups.sort_by(|a, b| b.id.cmp(&a.id));
+        backups
+    }
+
+    pub fn find(&self, id: &str) -> Result<Backup, String> {
+        self.list()
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| format!("No backup {} in {}, see `aws-sso-navigator backup list`", id, self.dir.display()))
+    }
+
+    /// Puts the backup back in place of the file it was taken from, backing up
+    /// that file first so the restore can be undone.
+    pub fn restore(&self, backup: &Backup) -> Result<(), String> {
+        let contents = fs::read(&backup.path)
+            .map_err(|e| format!("Failed to read backup {}: {}", backup.id, e))?;
+        self.back_up(&backup.source)?;
+        write_atomic(&backup.source, &contents)
+            .map_err(|e| format!("Failed to write {}: {}", backup.source.display(), e))
+    }
+}
+
+/// Whether `config_path` is the file `backup` was taken from.
+pub fn is_backup_of(backup: &Backup, config_path: &Path) -> bool {
+    source_path(config_path) == backup.source
+}
+
+/// The absolute path of `config_path` with symlinks resolved, so one file
+/// always has the same backups however it's reached.
+fn source_path(config_path: &Path) -> PathBuf {
+    fs::canonicalize(config_path)
+        .or_else(|_| std::path::absolute(config_path))
+        .unwrap_or_else(|_| config_path.to_path_buf())
+}
+
+/// A unified diff from the backup's file as it is now to the backup, empty if they match.
+pub fn restore_diff(backup: &Backup) -> Result<String, String> {
+    let current = fs::read_to_string(&backup.source).unwrap_or_default();
+    let restored = fs::read_to_string(&backup.path)
+        .map_err(|e| format!("Failed to read backup {}: {}", backup.id, e))?;
+    if current == restored {
+        return Ok(String::new());
+    }
+    Ok(TextDiff::from_lines(&current, &restored)
+        .unified_diff()
+        .header(&backup.source.display().to_string(), &format!("backup {}", backup.id))
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_back_up_rotates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let backups = Backups::new(temp_dir.path().join("backups"), 2);
+
+        // Nothing to back up before the config exists
+        assert_eq!(backups.back_up(&config_path).unwrap(), None);
+
+        for contents in ["first", "second", "third"] {
+            fs::write(&config_path, contents).unwrap();
+            assert!(backups.back_up(&config_path).unwrap().is_some());
+        }
+        // Unchanged since the last backup
+        assert_eq!(backups.back_up(&config_path).unwrap(), None);
+
+        let listed = backups.list();
+        let contents: Vec<String> = listed.iter().map(|b| fs::read_to_string(&b.path).unwrap()).collect();
+        assert_eq!(contents, vec!["third", "second"]);
+        assert!(listed[0].created_at >= listed[1].created_at);
+        assert_eq!(backups.find(&listed[1].id).unwrap(), backups.list().remove(1));
+        assert!(backups.find("20000101-000000.000").is_err());
+    }
+
+    #[test]
+    fn test_backups_are_kept_per_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let work = temp_dir.path().join("work");
+        let personal = temp_dir.path().join("personal");
+        let backups = Backups::new(temp_dir.path().join("backups"), 1);
+
+        fs::write(&work, "[default]\n").unwrap();
+        fs::write(&personal, "[default]\n").unwrap();
+        let work_backup = backups.back_up(&work).unwrap().unwrap();
+        // Same contents, but another file
+        let personal_backup = backups.back_up(&personal).unwrap().unwrap();
+        assert_ne!(work_backup.id, personal_backup.id);
+        // The limit applies to each file on its own
+        assert_eq!(backups.list().len(), 2);
+
+        assert_eq!(backups.find(&work_backup.id).unwrap().source, fs::canonicalize(&work).unwrap());
+        assert!(is_backup_of(&work_backup, &work));
+        assert!(!is_backup_of(&work_backup, &personal));
+
+        // Restoring goes back to the file the backup came from
+        fs::write(&work, "[profile changed]\n").unwrap();
+        backups.restore(&work_backup).unwrap();
+        assert_eq!(fs::read_to_string(&work).unwrap(), "[default]\n");
+        assert_eq!(fs::read_to_string(&personal).unwrap(), "[default]\n");
+    }
+
+    #[test]
+    fn test_backups_disabled() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, "[default]\n").unwrap();
+
+        let backups = Backups::new(temp_dir.path().join("backups"), 0);
+        assert_eq!(backups.back_up(&config_path).unwrap(), None);
+        assert!(!temp_dir.path().join("backups").exists());
+    }
+
+    #[test]
+    fn test_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let backups = Backups::new(temp_dir.path().join("backups"), 10);
+        fs::write(&config_path, "[profile a]\nregion = us-east-1\n[profile b]\n").unwrap();
+        let before_import = backups.back_up(&config_path).unwrap().unwrap();
+        assert_eq!(before_import.profile_count(), 2);
+        fs::write(&config_path, "[profile c]\n").unwrap();
+
+        assert!(is_backup_of(&before_import, &config_path));
+        let diff = restore_diff(&before_import).unwrap();
+        assert!(diff.contains("-[profile c]\n"), "{}", diff);
+        assert!(diff.contains("+[profile a]\n+region = us-east-1\n+[profile b]\n"), "{}", diff);
+
+        backups.restore(&before_import).unwrap();
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), "[profile a]\nregion = us-east-1\n[profile b]\n");
+        assert_eq!(restore_diff(&before_import).unwrap(), "");
+
+        // The overwritten config was backed up too
+        let newest = &backups.list()[0];
+        assert_eq!(fs::read_to_string(&newest.path).unwrap(), "[profile c]\n");
+    }
+}
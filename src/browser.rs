@@ -0,0 +1,224 @@
+use std::path::Path;
+use std::process::Command;
+
+const URL_PLACEHOLDER: &str = "{url}";
+
+/// A command that opens URLs: the `browser` template from the settings, or the
+/// platform's opener.
+#[derive(Debug, PartialEq)]
+pub struct Opener {
+    /// Program and arguments, with `{url}` where the URL goes.
+    argv: Vec<String>,
+}
+
+impl Opener {
+    /// Parses a template like `firefox --private-window {url}`. Without a
+    /// `{url}` placeholder the URL is appended. A path to an existing file is
+    /// used as the program as-is, so paths with spaces keep working unquoted.
+    pub fn new(template: Option<&str>) -> Result<Self, String> {
+        let argv = match template {
+            None => default_argv(),
+            Some(template) if Path::new(template).is_file() => {
+                vec![template.to_string(), URL_PLACEHOLDER.to_string()]
+            }
+            Some(template) => {
+                let mut argv = split_command(template)?;
+                if argv.is_empty() {
+                    return Err("browser command is empty".to_string());
+                }
+                if !argv.iter().any(|arg| arg.contains(URL_PLACEHOLDER)) {
+                    argv.push(URL_PLACEHOLDER.to_string());
+                }
+                argv
+            }
+        };
+        Ok(Self { argv })
+    }
+
+    fn args_for(&self, url: &str) -> Vec<String> {
+        self.argv
+            .iter()
+            .map(|arg| arg.replace(URL_PLACEHOLDER, url))
+            .collect()
+    }
+
+    pub fn open(&self, url: &str) -> Result<(), String> {
+        let args = self.args_for(url);
+        let status = Command::new(&args[0])
+            .args(&args[1..])
+            .status()
+            .map_err(|e| format!("Failed to run {}: {}", args[0], e))?;
+
+        if !status.success() {
+            return Err(format!("{} exited with {}", args[0], status));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn default_argv() -> Vec<String> {
+    vec!["open".to_string(), URL_PLACEHOLDER.to_string()]
+}
+
+#[cfg(not(target_os = "macos"))]
+fn default_argv() -> Vec<String> {
+    vec!["xdg-open".to_string(), URL_PLACEHOLDER.to_string()]
+}
+
+/// Opens `url` with the configured browser or the platform's opener, and
+/// prints the URL instead if that fails.
+pub fn open_url(url: &str, template: Option<&str>) {
+    if let Err(e) = Opener::new(template).and_then(|opener| opener.open(url)) {
+        eprintln!("Couldn't open a browser ({}), open this URL manually: {}", e, url);
+    }
+}
+
+/// Value for `BROWSER` that makes `aws sso login` use the configured browser.
+/// The AWS CLI opens URLs with Python's webbrowser module, which splits a
+/// `BROWSER` containing `%s` into arguments and puts the URL there.
+pub fn browser_env(template: Option<&str>) -> Option<String> {
+    let template = template?;
+    if Path::new(template).is_file() || !template.contains(char::is_whitespace) {
+        return Some(template.to_string());
+    }
+    if template.contains(URL_PLACEHOLDER) {
+        Some(template.replace(URL_PLACEHOLDER, "%s"))
+    } else {
+        Some(format!("{} %s", template))
+    }
+}
+
+/// Splits a command line into words, honoring single and double quotes and
+/// backslash escapes like a POSIX shell.
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unclosed quote in browser command: {}", command)),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("Unclosed quote in browser command: {}", command)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("Unclosed quote in browser command: {}", command)),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"google-chrome --profile-directory="Work" {url}"#).unwrap(),
+            vec!["google-chrome", "--profile-directory=Work", "{url}"]
+        );
+        assert_eq!(
+            split_command(r"'/opt/My Browser/browser' --new\ tab ''").unwrap(),
+            vec!["/opt/My Browser/browser", "--new tab", ""]
+        );
+        assert!(split_command("firefox 'oops").is_err());
+    }
+
+    #[test]
+    fn test_opener_templates() {
+        let url = "https://example.com/?a=1&b=2";
+
+        let opener = Opener::new(Some("firefox --private-window {url}")).unwrap();
+        assert_eq!(opener.args_for(url), vec!["firefox", "--private-window", url]);
+
+        let opener = Opener::new(Some("chromium --app={url} --new-window")).unwrap();
+        assert_eq!(
+            opener.args_for(url),
+            vec!["chromium".to_string(), format!("--app={}", url), "--new-window".to_string()]
+        );
+
+        // Without a placeholder the URL goes last
+        let opener = Opener::new(Some("firefox")).unwrap();
+        assert_eq!(opener.args_for(url), vec!["firefox", url]);
+
+        let default = Opener::new(None).unwrap();
+        assert_eq!(default.args_for(url)[1], url);
+        assert!(Opener::new(Some("  ")).is_err());
+    }
+
+    #[test]
+    fn test_opener_accepts_unquoted_path_with_spaces() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let browser = temp_dir.path().join("Google Chrome");
+        std::fs::write(&browser, "").unwrap();
+        let template = browser.to_str().unwrap();
+
+        let opener = Opener::new(Some(template)).unwrap();
+        assert_eq!(opener.args_for("https://example.com"), vec![template, "https://example.com"]);
+        assert_eq!(browser_env(Some(template)).as_deref(), Some(template));
+    }
+
+    #[test]
+    fn test_open_fails_for_missing_program() {
+        let opener = Opener::new(Some("/nonexistent/browser {url}")).unwrap();
+        assert!(opener.open("https://example.com").unwrap_err().starts_with("Failed to run /nonexistent/browser"));
+    }
+
+    #[test]
+    fn test_browser_env() {
+        assert_eq!(browser_env(None), None);
+        assert_eq!(browser_env(Some("firefox")).as_deref(), Some("firefox"));
+        assert_eq!(
+            browser_env(Some("firefox --private-window {url}")).as_deref(),
+            Some("firefox --private-window %s")
+        );
+        assert_eq!(
+            browser_env(Some(r#"google-chrome --profile-directory="Work""#)).as_deref(),
+            Some(r#"google-chrome --profile-directory="Work" %s"#)
+        );
+    }
+}
@@ -2,6 +2,7 @@ use dirs::home_dir;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Serialize, Deserialize, Default)]
@@ -14,11 +15,48 @@ pub struct Settings {
     pub list: Option<bool>,
     pub recent: Option<bool>,
     pub max_recent_profiles: Option<usize>,
+    /// Backups of each AWS config file to keep, 0 to turn them off.
+    pub max_backups: Option<usize>,
     pub aws_config_path: Option<String>,
+    pub extra_config_paths: Option<Vec<String>>,
     pub force_reauth: Option<bool>,
     pub check_session: Option<bool>,
+    /// Seconds before `expiresAt` at which a cached SSO token counts as expired.
+    pub session_skew_seconds: Option<i64>,
+    /// Confirm cached sessions with `aws sts get-caller-identity`.
+    pub deep_session_check: Option<bool>,
     pub browser: Option<String>,
+    /// Log in with the SSO OIDC device flow instead of `aws sso login`.
+    pub native_login: Option<bool>,
+    /// Base URL of the SSO OIDC service, e.g. for a local mock server.
+    pub sso_oidc_endpoint: Option<String>,
+    /// Base URL of the SSO portal API that issues role credentials.
+    pub sso_portal_endpoint: Option<String>,
     pub tree: Option<bool>,
+    pub naming: Option<NamingSettings>,
+    /// Display names for path segments, keyed by level name then raw segment.
+    pub aliases: Option<HashMap<String, HashMap<String, String>>>,
+    /// Open the console in Firefox Multi-Account Containers.
+    pub firefox_containers: Option<bool>,
+    /// Container for each client, keyed by the first segment of the profile path.
+    pub containers: Option<HashMap<String, ContainerSettings>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct NamingSettings {
+    pub levels: Option<Vec<String>>,
+    pub pattern: Option<String>,
+    pub separator: Option<String>,
+    /// Called `fallback_client` before levels were configurable.
+    #[serde(alias = "fallback_client")]
+    pub fallback: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ContainerSettings {
+    pub name: Option<String>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -41,6 +79,60 @@ pub fn load_settings() -> Settings {
     }
 }
 
+/// Picks the AWS config file: the `--aws-config-path` flag, then `AWS_CONFIG_FILE`,
+/// then `aws_config_path` from the settings, then `~/.aws/config`.
+pub fn resolve_aws_config_path(flag: Option<PathBuf>, settings: &Settings) -> PathBuf {
+    let env = std::env::var_os("AWS_CONFIG_FILE")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    choose_aws_config_path(flag, env, settings, &home_dir().unwrap())
+}
+
+fn choose_aws_config_path(
+    flag: Option<PathBuf>,
+    env: Option<PathBuf>,
+    settings: &Settings,
+    home: &Path,
+) -> PathBuf {
+    flag.or(env)
+        .or_else(|| settings.aws_config_path.as_deref().map(PathBuf::from))
+        .map(|path| expand_home(&path, home))
+        .unwrap_or_else(|| home.join(".aws").join("config"))
+}
+
+/// All config files to load profiles from: the AWS config itself, then
+/// `extra_config_paths` from the settings, then `config.d/*.ini` next to the AWS config.
+pub fn config_sources(aws_config_path: &Path, settings: &Settings) -> Vec<PathBuf> {
+    let home = home_dir().unwrap();
+    let mut sources = vec![aws_config_path.to_path_buf()];
+
+    for path in settings.extra_config_paths.iter().flatten() {
+        sources.push(expand_home(Path::new(path), &home));
+    }
+
+    if let Some(config_dir) = aws_config_path.parent()
+        && let Ok(entries) = fs::read_dir(config_dir.join("config.d"))
+    {
+        let mut fragments: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "ini"))
+            .collect();
+        fragments.sort();
+        sources.extend(fragments);
+    }
+
+    sources
+}
+
+/// Expands a leading `~` the way a shell would.
+fn expand_home(path: &Path, home: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home.join(rest),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
 pub fn load_recent_profiles() -> RecentProfiles {
     let config_dir = home_dir()
         .unwrap()
@@ -83,4 +175,62 @@ pub fn save_recent_profile(profile_name: &str, max_entries: usize) {
     if let Ok(contents) = toml::to_string(&recent) {
         fs::write(&recent_path, contents).ok();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_sources() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        let config_d = temp_dir.path().join("config.d");
+        fs::create_dir(&config_d).unwrap();
+        fs::write(config_d.join("b-team.ini"), "").unwrap();
+        fs::write(config_d.join("a-shared.ini"), "").unwrap();
+        fs::write(config_d.join("notes.txt"), "").unwrap();
+
+        let settings = Settings {
+            extra_config_paths: Some(vec!["/srv/shared/aws-config".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            config_sources(&config_path, &settings),
+            vec![
+                config_path.clone(),
+                PathBuf::from("/srv/shared/aws-config"),
+                config_d.join("a-shared.ini"),
+                config_d.join("b-team.ini"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_choose_aws_config_path_precedence() {
+        let home = Path::new("/home/user");
+        let settings = Settings {
+            aws_config_path: Some("~/work/aws-config".to_string()),
+            ..Default::default()
+        };
+        let flag = Some(PathBuf::from("/flag/config"));
+        let env = Some(PathBuf::from("/env/config"));
+
+        assert_eq!(
+            choose_aws_config_path(flag.clone(), env.clone(), &settings, home),
+            PathBuf::from("/flag/config")
+        );
+        assert_eq!(
+            choose_aws_config_path(None, env, &settings, home),
+            PathBuf::from("/env/config")
+        );
+        assert_eq!(
+            choose_aws_config_path(None, None, &settings, home),
+            PathBuf::from("/home/user/work/aws-config")
+        );
+        assert_eq!(
+            choose_aws_config_path(None, None, &Settings::default(), home),
+            PathBuf::from("/home/user/.aws/config")
+        );
+    }
+}
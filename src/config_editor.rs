@@ -0,0 +1,390 @@
+use crate::atomic_file::write_atomic;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// An AWS config file edited line by line, so comments, blank lines, key order
+/// and formatting outside the edited keys survive a write.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigDocument {
+    /// Lines without their `\n`. A `\r` before it is kept, so CRLF files are
+    /// written back as they were.
+    lines: Vec<String>,
+    /// Lines added to the document end in CRLF.
+    crlf: bool,
+    /// The file didn't end with a newline, and is written back without one.
+    no_final_newline: bool,
+}
+
+impl ConfigDocument {
+    pub fn parse(contents: &str) -> Self {
+        let mut lines: Vec<String> = contents.split('\n').map(String::from).collect();
+        // `split` leaves an empty string after a final newline
+        let no_final_newline = lines.pop_if(|line| line.is_empty()).is_none() && !lines.is_empty();
+        let crlf = lines.first().is_some_and(|line| line.ends_with('\r'));
+        if crlf && no_final_newline {
+            // Like the lines before it, in case more get added after it
+            lines.last_mut().unwrap().push('\r');
+        }
+        Self {
+            lines,
+            crlf,
+            no_final_newline,
+        }
+    }
+
+    /// Loads the file at `path`, or an empty document if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Writes the document to `path` through a temporary file and a rename.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        write_atomic(path, self.to_string().as_bytes())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn has_section(&self, section: &str) -> bool {
+        self.section_range(section).is_some()
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<String> {
+        self.entries(section)
+            .into_iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// The keys and values of `section` in file order. Nested values, like
+    /// `s3 =` followed by indented lines, keep those lines as they are.
+    pub fn entries(&self, section: &str) -> Vec<(String, String)> {
+        let Some(range) = self.section_range(section) else {
+            return Vec::new();
+        };
+        self.entry_ranges(range)
+            .into_iter()
+            .map(|(key, lines)| {
+                let first = &self.lines[lines.start];
+                let mut value = first[first.find('=').unwrap() + 1..].trim().to_string();
+                for line in &self.lines[lines.start + 1..lines.end] {
+                    value.push('\n');
+                    value.push_str(line.trim_end_matches('\r'));
+                }
+                (key, value)
+            })
+            .collect()
+    }
+
+    /// Sets `key` in `section`. An existing key is replaced where it is, keeping
+    /// its spacing around `=`. A new key goes after the section's last key, and
+    /// a missing section is added at the end.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let Some(range) = self.section_range(section) else {
+            self.append_section(section, &[(key, value)]);
+            return;
+        };
+        let entries = self.entry_ranges(range.clone());
+
+        if let Some((_, lines)) = entries.iter().find(|(k, _)| k == key) {
+            let line = &self.lines[lines.start];
+            let separator = line.find('=').unwrap() + 1;
+            let spacing = line[separator..].len() - line[separator..].trim_start().len();
+            let prefix = line[..separator + spacing].to_string();
+            let new_lines = self.with_line_endings(entry_lines(&prefix, value));
+            self.lines.splice(lines.clone(), new_lines);
+        } else {
+            let at = entries.last().map_or(range.start + 1, |(_, lines)| lines.end);
+            let new_lines = self.with_line_endings(entry_lines(&format!("{} = ", key), value));
+            self.lines.splice(at..at, new_lines);
+        }
+    }
+
+    /// Removes `section` with its keys. Comments right before the next section
+    /// are kept, since they usually describe that one. Returns whether the
+    /// section was there.
+    pub fn remove_section(&mut self, section: &str) -> bool {
+        let Some(range) = self.section_range(section) else {
+            return false;
+        };
+
+        let mut end = range.end;
+        if end < self.lines.len() {
+            // Keep the comment block above the next header, dropping the
+            // blank lines between it and the removed keys
+            let mut start = end;
+            while start > range.start + 1 && is_blank_or_comment(&self.lines[start - 1]) {
+                start -= 1;
+            }
+            end = (start..range.end)
+                .find(|&i| is_comment(&self.lines[i]))
+                .unwrap_or(range.end);
+        }
+        self.lines.drain(range.start..end);
+
+        if range.start >= self.lines.len() {
+            while self.lines.last().is_some_and(|line| line.trim().is_empty()) {
+                self.lines.pop();
+            }
+        }
+        true
+    }
+
+    /// Adds a section at the end, separated from what's before by a blank line.
+    pub fn append_section(&mut self, section: &str, entries: &[(&str, &str)]) {
+        let mut new_lines = Vec::new();
+        if self.lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            new_lines.push(String::new());
+        }
+        new_lines.push(format!("[{}]", section));
+        for (key, value) in entries {
+            new_lines.extend(entry_lines(&format!("{} = ", key), value));
+        }
+        let new_lines = self.with_line_endings(new_lines);
+        self.lines.extend(new_lines);
+    }
+
+    /// Adds the `\r` of CRLF files to new lines.
+    fn with_line_endings(&self, lines: Vec<String>) -> Vec<String> {
+        lines
+            .into_iter()
+            .map(|line| {
+                let line = line.trim_end_matches('\r');
+                if self.crlf { format!("{}\r", line) } else { line.to_string() }
+            })
+            .collect()
+    }
+
+    /// Lines of `section`, from its header up to the next header.
+    fn section_range(&self, section: &str) -> Option<Range<usize>> {
+        let start = self
+            .lines
+            .iter()
+            .position(|line| section_name(line).as_deref() == Some(section))?;
+        let end = self.lines[start + 1..]
+            .iter()
+            .position(|line| section_name(line).is_some())
+            .map_or(self.lines.len(), |offset| start + 1 + offset);
+        Some(start..end)
+    }
+
+    /// Each key in `section_range` with its line and any indented lines after it.
+    fn entry_ranges(&self, section_range: Range<usize>) -> Vec<(String, Range<usize>)> {
+        let mut entries: Vec<(String, Range<usize>)> = Vec::new();
+        for i in section_range.start + 1..section_range.end {
+            let line = &self.lines[i];
+            let indented = line.starts_with(char::is_whitespace) && !line.trim().is_empty();
+            match entries.last_mut() {
+                Some((_, lines)) if indented && lines.end == i => lines.end = i + 1,
+                _ if indented || is_blank_or_comment(line) => {}
+                _ => {
+                    if let Some((key, _)) = line.split_once('=') {
+                        entries.push((key.trim().to_string(), i..i + 1));
+                    }
+                }
+            }
+        }
+        entries
+    }
+}
+
+impl fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, line) in self.lines.iter().enumerate() {
+            if i + 1 < self.lines.len() || !self.no_final_newline {
+                writeln!(f, "{}", line)?;
+            } else {
+                write!(f, "{}", line.trim_end_matches('\r'))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name in a `[section]` header, with runs of whitespace collapsed.
+fn section_name(line: &str) -> Option<String> {
+    let inner = line.trim().strip_prefix('[')?;
+    let (name, _) = inner.split_once(']')?;
+    Some(name.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn is_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('#') || line.starts_with(';')
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    line.trim().is_empty() || is_comment(line)
+}
+
+/// `prefix` plus the first line of `value`, then the rest of its lines as is.
+fn entry_lines(prefix: &str, value: &str) -> Vec<String> {
+    let mut lines = value.split('\n');
+    let first = format!("{}{}", prefix, lines.next().unwrap_or_default());
+    std::iter::once(first.trim_end().to_string())
+        .chain(lines.map(String::from))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Managed by hand, keep the comments
+[default]
+region=us-east-1
+
+[profile client-dev-admin]
+sso_session = example
+sso_account_id = 123456789012
+s3 =
+  max_concurrent_requests = 20
+sso_role_name = Admin
+
+; shared tooling
+[sso-session   example]
+sso_start_url = https://example.awsapps.com/start
+";
+
+    #[test]
+    fn test_round_trip_is_unchanged() {
+        assert_eq!(ConfigDocument::parse(CONFIG).to_string(), CONFIG);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_line_endings() {
+        let crlf = CONFIG.replace('\n', "\r\n");
+        assert_eq!(ConfigDocument::parse(&crlf).to_string(), crlf);
+        let no_final_newline = CONFIG.trim_end();
+        assert_eq!(ConfigDocument::parse(no_final_newline).to_string(), no_final_newline);
+        let crlf_no_final_newline = crlf.trim_end();
+        assert_eq!(ConfigDocument::parse(crlf_no_final_newline).to_string(), crlf_no_final_newline);
+        assert_eq!(ConfigDocument::parse("").to_string(), "");
+    }
+
+    #[test]
+    fn test_edits_follow_line_endings() {
+        let mut document = ConfigDocument::parse("[default]\r\nregion = us-east-1\r\ns3 =\r\n  addressing_style = path\r\n");
+        assert_eq!(document.get("default", "region").as_deref(), Some("us-east-1"));
+        assert_eq!(document.get("default", "s3").as_deref(), Some("\n  addressing_style = path"));
+        document.set("default", "region", "eu-west-1");
+        document.set("default", "output", "json");
+        document.append_section("profile a", &[("region", "us-west-2")]);
+        assert_eq!(
+            document.to_string(),
+            "[default]\r\nregion = eu-west-1\r\ns3 =\r\n  addressing_style = path\r\noutput = json\r\n\r\n\
+             [profile a]\r\nregion = us-west-2\r\n"
+        );
+
+        let mut document = ConfigDocument::parse("[default]\nregion = us-east-1");
+        document.set("default", "output", "json");
+        assert_eq!(document.to_string(), "[default]\nregion = us-east-1\noutput = json");
+        let mut document = ConfigDocument::parse("[default]\r\nregion = us-east-1");
+        document.set("default", "output", "json");
+        assert_eq!(document.to_string(), "[default]\r\nregion = us-east-1\r\noutput = json");
+    }
+
+    #[test]
+    fn test_entries() {
+        let document = ConfigDocument::parse(CONFIG);
+        assert_eq!(
+            document.entries("profile client-dev-admin"),
+            vec![
+                ("sso_session".to_string(), "example".to_string()),
+                ("sso_account_id".to_string(), "123456789012".to_string()),
+                ("s3".to_string(), "\n  max_concurrent_requests = 20".to_string()),
+                ("sso_role_name".to_string(), "Admin".to_string()),
+            ]
+        );
+        assert!(document.has_section("sso-session example"));
+        assert_eq!(
+            document.get("sso-session example", "sso_start_url").as_deref(),
+            Some("https://example.awsapps.com/start")
+        );
+        assert_eq!(document.get("default", "output"), None);
+    }
+
+    #[test]
+    fn test_set_edits_in_place() {
+        let mut document = ConfigDocument::parse(CONFIG);
+        document.set("default", "region", "eu-west-1");
+        document.set("default", "output", "json");
+        document.set("profile client-dev-admin", "s3", "\n  max_concurrent_requests = 5");
+        document.set("profile new", "region", "us-west-2");
+
+        assert_eq!(
+            document.to_string(),
+            "\
+# Managed by hand, keep the comments
+[default]
+region=eu-west-1
+output = json
+
+[profile client-dev-admin]
+sso_session = example
+sso_account_id = 123456789012
+s3 =
+  max_concurrent_requests = 5
+sso_role_name = Admin
+
+; shared tooling
+[sso-session   example]
+sso_start_url = https://example.awsapps.com/start
+
+[profile new]
+region = us-west-2
+"
+        );
+    }
+
+    #[test]
+    fn test_remove_section() {
+        let mut document = ConfigDocument::parse(CONFIG);
+        assert!(document.remove_section("profile client-dev-admin"));
+        assert!(!document.remove_section("profile client-dev-admin"));
+        assert_eq!(
+            document.to_string(),
+            "\
+# Managed by hand, keep the comments
+[default]
+region=us-east-1
+
+; shared tooling
+[sso-session   example]
+sso_start_url = https://example.awsapps.com/start
+"
+        );
+
+        // Comments above a removed section are left alone
+        assert!(document.remove_section("sso-session example"));
+        assert_eq!(
+            document.to_string(),
+            "# Managed by hand, keep the comments\n[default]\nregion=us-east-1\n\n; shared tooling\n"
+        );
+        // With no section after it, everything up to the end goes
+        assert!(document.remove_section("default"));
+        assert_eq!(document.to_string(), "# Managed by hand, keep the comments\n");
+    }
+
+    #[test]
+    fn test_load_missing_file_and_save() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config");
+        let mut document = ConfigDocument::load(&path).unwrap();
+        assert_eq!(document, ConfigDocument::default());
+
+        document.append_section("profile a", &[("region", "us-east-1")]);
+        document.append_section("profile b", &[("region", "eu-west-1")]);
+        document.save(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[profile a]\nregion = us-east-1\n\n[profile b]\nregion = eu-west-1\n"
+        );
+    }
+}
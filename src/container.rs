@@ -0,0 +1,103 @@
+use crate::aws::url_encode;
+use crate::config::ContainerSettings;
+use crate::profile::Profile;
+use std::collections::HashMap;
+
+/// A Firefox Multi-Account Container to open a URL in.
+#[derive(Debug, PartialEq)]
+pub struct Container {
+    pub name: String,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+}
+
+impl Container {
+    /// The container for the profile's client, the first segment of its path.
+    /// Clients without settings get a container named after the client.
+    pub fn for_profile(profile: &Profile, settings: Option<&HashMap<String, ContainerSettings>>) -> Self {
+        let client = profile.path.first().unwrap_or(&profile.name);
+        let client_settings = settings.and_then(|containers| containers.get(client));
+        Self {
+            name: client_settings
+                .and_then(|c| c.name.clone())
+                .unwrap_or_else(|| client.clone()),
+            color: client_settings.and_then(|c| c.color.clone()),
+            icon: client_settings.and_then(|c| c.icon.clone()),
+        }
+    }
+
+    /// Wraps `url` in the `ext+container:` scheme, which the "Open external
+    /// links in a container" extension opens in this container, creating it
+    /// if needed.
+    pub fn wrap_url(&self, url: &str) -> String {
+        let mut wrapped = format!("ext+container:name={}&url={}", url_encode(&self.name), url_encode(url));
+        if let Some(color) = &self.color {
+            wrapped.push_str(&format!("&color={}", url_encode(color)));
+        }
+        if let Some(icon) = &self.icon {
+            wrapped.push_str(&format!("&icon={}", url_encode(icon)));
+        }
+        wrapped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(path: &[&str]) -> Profile {
+        Profile {
+            name: path.join("-"),
+            path: path.iter().map(|segment| segment.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_container_for_profile() {
+        let settings = HashMap::from([(
+            "acme".to_string(),
+            ContainerSettings {
+                name: Some("Acme Corp".to_string()),
+                color: Some("orange".to_string()),
+                icon: None,
+            },
+        )]);
+
+        assert_eq!(
+            Container::for_profile(&profile(&["acme", "prod", "admin"]), Some(&settings)),
+            Container {
+                name: "Acme Corp".to_string(),
+                color: Some("orange".to_string()),
+                icon: None,
+            }
+        );
+        assert_eq!(
+            Container::for_profile(&profile(&["globex", "dev", "admin"]), Some(&settings)).name,
+            "globex"
+        );
+        assert_eq!(Container::for_profile(&profile(&["globex", "dev", "admin"]), None).color, None);
+    }
+
+    #[test]
+    fn test_wrap_url() {
+        let container = Container {
+            name: "Acme Corp".to_string(),
+            color: Some("orange".to_string()),
+            icon: Some("briefcase".to_string()),
+        };
+        assert_eq!(
+            container.wrap_url("https://example.awsapps.com/start/#/console?account_id=123456789012&role_name=Admin"),
+            "ext+container:name=Acme%20Corp\
+             &url=https%3A%2F%2Fexample.awsapps.com%2Fstart%2F%23%2Fconsole%3Faccount_id%3D123456789012%26role_name%3DAdmin\
+             &color=orange&icon=briefcase"
+        );
+
+        let plain = Container {
+            name: "globex".to_string(),
+            color: None,
+            icon: None,
+        };
+        assert_eq!(plain.wrap_url("https://example.com"), "ext+container:name=globex&url=https%3A%2F%2Fexample.com");
+    }
+}
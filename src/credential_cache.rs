@@ -0,0 +1,87 @@
+use crate::atomic_file::write_secret;
+use crate::portal::RoleCredentials;
+use chrono::{DateTime, Duration, Utc};
+use dirs::home_dir;
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where `credential-process` keeps role credentials between calls.
+pub fn cache_dir() -> PathBuf {
+    home_dir()
+        .unwrap()
+        .join(".config")
+        .join("aws-sso-navigator")
+        .join("credentials")
+}
+
+/// Cache file for a profile, named by the SHA-1 of the profile name so any
+/// name makes a valid file name.
+pub fn credentials_path(cache_dir: &Path, profile_name: &str) -> PathBuf {
+    let digest = Sha1::digest(profile_name.as_bytes());
+    cache_dir.join(format!("{:x}.json", digest))
+}
+
+/// Returns the cached credentials if they're still valid at `now`, counting
+/// credentials that expire within `skew` as expired.
+pub fn read_credentials(path: &Path, now: DateTime<Utc>, skew: Duration) -> Option<RoleCredentials> {
+    let contents = fs::read(path).ok()?;
+    RoleCredentials::from_process_json(&contents)
+        .ok()
+        .filter(|credentials| credentials.expiration - skew > now)
+}
+
+pub fn write_credentials(path: &Path, credentials: &RoleCredentials) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    }
+    // Renamed into place, so a concurrent `credential-process` never reads half the JSON
+    write_secret(path, credentials.to_process_json().as_bytes())
+        .map_err(|e| format!("Failed to write credentials cache: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(expiration: &str) -> RoleCredentials {
+        RoleCredentials {
+            access_key_id: "AKIA".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: "session".to_string(),
+            expiration: DateTime::parse_from_rfc3339(expiration).unwrap().to_utc(),
+        }
+    }
+
+    #[test]
+    fn test_cached_credentials_expire() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = credentials_path(temp_dir.path(), "client-dev-admin");
+        let now = DateTime::parse_from_rfc3339("2030-01-01T00:00:00Z").unwrap().to_utc();
+
+        assert_eq!(read_credentials(&path, now, Duration::zero()), None);
+
+        let cached = credentials("2030-01-01T00:10:00Z");
+        write_credentials(&path, &cached).unwrap();
+        assert_eq!(read_credentials(&path, now, Duration::zero()), Some(cached));
+        assert_eq!(read_credentials(&path, now, Duration::minutes(10)), None);
+        assert_eq!(read_credentials(&path, now + Duration::hours(1), Duration::zero()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cached_credentials_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = credentials_path(temp_dir.path(), "client-dev-admin");
+        write_credentials(&path, &credentials("2030-01-01T00:10:00Z")).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Replaced through a temporary file, which is gone afterwards
+        write_credentials(&path, &credentials("2030-01-01T01:10:00Z")).unwrap();
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -1,125 +1,69 @@
-use std::process::Command;
-use std::fs;
-use std::path::PathBuf;
-use dirs::home_dir;
-use serde::Deserialize;
-use ini::Ini;
+use std::path::Path;
 use indicatif::{ProgressBar, ProgressStyle};
-
-#[derive(Deserialize)]
-struct AccountList {
-    #[serde(rename = "accountList")]
-    account_list: Vec<Account>,
-}
-
-#[derive(Deserialize)]
-struct Account {
-    #[serde(rename = "accountId")]
-    account_id: String,
-    #[serde(rename = "accountName")]
-    account_name: String,
-}
-
-#[derive(Deserialize)]
-struct RoleList {
-    #[serde(rename = "roleList")]
-    role_list: Vec<Role>,
-}
-
-#[derive(Deserialize)]
-struct Role {
-    #[serde(rename = "roleName")]
-    role_name: String,
-    #[serde(rename = "accountId")]
-    account_id: String,
-}
-
-#[derive(Deserialize)]
-struct SsoToken {
-    #[serde(rename = "accessToken")]
-    access_token: String,
-}
-
-pub fn import_profiles(sso_session: &str, config_path: &PathBuf) -> Result<(), String> {
-    // Login to SSO session
-    let status = Command::new("aws")
-        .args(["sso", "login", "--sso-session", sso_session])
-        .status()
-        .map_err(|e| format!("Failed to execute aws: {}", e))?;
-
-    if !status.success() {
-        return Err("AWS SSO login failed".to_string());
-    }
-
-    // Get access token from cache
-    let cache_dir = home_dir().unwrap().join(".aws/sso/cache");
-    let mut cache_files: Vec<_> = fs::read_dir(&cache_dir)
-        .map_err(|e| format!("Failed to read cache directory: {}", e))?
-        .filter_map(|entry| entry.ok())
-        .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "json"))
-        .collect();
-
-    cache_files.sort_by_key(|entry| entry.metadata().unwrap().modified().unwrap());
-    
-    let token_file = cache_files.last()
-        .ok_or("No token file found")?;
-
-    let token_content = fs::read_to_string(token_file.path())
-        .map_err(|e| format!("Failed to read token file: {}", e))?;
-    
-    let token: SsoToken = serde_json::from_str(&token_content)
-        .map_err(|e| format!("Failed to parse token: {}", e))?;
+use crate::backend::Backend;
+use crate::backup::Backups;
+use crate::config_editor::ConfigDocument;
+use crate::profile::Profile;
+
+/// Logs in to `sso_session` and appends a profile for every account and role
+/// it can access that isn't in the config yet. Accounts whose roles can't be
+/// listed are skipped with a warning; the rest are still saved, but the import
+/// returns an error naming the skipped ones.
+pub fn import_profiles(backend: &dyn Backend, sso_session: &str, config_path: &Path, backups: &Backups) -> Result<(), String> {
+    let mut config = ConfigDocument::load(config_path)?;
+    let session_section = format!("sso-session {}", sso_session);
 
     // Get region from sso-session
-    let ini = Ini::load_from_file(config_path)
-        .map_err(|e| format!("Failed to load config: {}", e))?;
-    
-    let sso_region = ini.section(Some(&format!("sso-session {}", sso_session)))
-        .and_then(|section| section.get("sso_region"))
+    let sso_region = config
+        .get(&session_section, "sso_region")
         .ok_or("SSO region not found in config")?;
-
-    // List accounts
-    let accounts_output = Command::new("aws")
-        .args(["sso", "list-accounts", "--region", sso_region, "--access-token", &token.access_token])
-        .output()
-        .map_err(|e| format!("Failed to list accounts: {}", e))?;
-
-    if !accounts_output.status.success() {
-        return Err("Failed to list accounts".to_string());
-    }
-
-    let accounts: AccountList = serde_json::from_slice(&accounts_output.stdout)
-        .map_err(|e| format!("Failed to parse accounts: {}", e))?;
-
-    let mut config_content = String::new();
-    let pb = ProgressBar::new(accounts.account_list.len() as u64);
+    let start_url = config
+        .get(&session_section, "sso_start_url")
+        .ok_or("SSO start URL not found in config")?;
+
+    // Logs in as the AWS CLI does for `--sso-session`
+    let session_profile = Profile {
+        name: sso_session.to_string(),
+        sso_session: Some(sso_session.to_string()),
+        sso_start_url: start_url,
+        sso_region: Some(sso_region.clone()),
+        source_file: config_path.to_path_buf(),
+        ..Default::default()
+    };
+    let access_token = backend.login(&session_profile)?.access_token;
+
+    let accounts = backend.list_accounts(&access_token, &sso_region)?;
+
+    let pb = ProgressBar::new(accounts.len() as u64);
     pb.set_style(ProgressStyle::default_bar()
         .template("{bar:40.cyan/blue} {pos}/{len} {msg}")
         .unwrap());
     pb.set_message("Processing accounts");
     
-    for account in &accounts.account_list {
-
-        let roles_output = Command::new("aws")
-            .args(["sso", "list-account-roles", "--region", sso_region, "--access-token", &token.access_token, "--account-id", &account.account_id])
-            .output()
-            .map_err(|e| format!("Failed to list roles: {}", e))?;
-
-        if !roles_output.status.success() {
-            continue;
-        }
-
-        let roles: RoleList = serde_json::from_slice(&roles_output.stdout)
-            .map_err(|e| format!("Failed to parse roles: {}", e))?;
+    let mut skipped = Vec::new();
+    for account in &accounts {
+        let roles = match backend.list_roles(&access_token, &sso_region, &account.account_id) {
+            Ok(roles) => roles,
+            Err(e) => {
+                pb.println(format!("Skipping account {} ({}): {}", account.account_name, account.account_id, e));
+                skipped.push(account.account_id.as_str());
+                pb.inc(1);
+                continue;
+            }
+        };
 
-        for role in &roles.role_list {
+        for role in &roles {
             let profile_name = format!("{}-{}-{}", sso_session, account.account_name.replace(' ', "").replace('-', "_"), role.role_name.replace('-', "_"));
             
-            if ini.section(Some(&format!("profile {}", profile_name))).is_none() {
-                config_content.push_str(&format!(
-                    "\n[profile {}]\nsso_session = {}\nsso_account_id = {}\nsso_role_name = {}\nregion = {}\noutput = json\n",
-                    profile_name, sso_session, role.account_id, role.role_name, sso_region
-                ));
+            let section = format!("profile {}", profile_name);
+            if !config.has_section(&section) {
+                config.append_section(&section, &[
+                    ("sso_session", sso_session),
+                    ("sso_account_id", &role.account_id),
+                    ("sso_role_name", &role.role_name),
+                    ("region", &sso_region),
+                    ("output", "json"),
+                ]);
             }
         }
         pb.inc(1);
@@ -127,8 +71,122 @@ pub fn import_profiles(sso_session: &str, config_path: &PathBuf) -> Result<(), S
     
     pb.finish_with_message("Import completed!");
 
-    fs::write(config_path, fs::read_to_string(config_path).unwrap_or_default() + &config_content)
-        .map_err(|e| format!("Failed to write config: {}", e))?;
-
+    backups.back_up(config_path)?;
+    config.save(config_path)?;
+    if !skipped.is_empty() {
+        return Err(format!(
+            "Couldn't list roles for {} of {} accounts, skipped {}",
+            skipped.len(),
+            accounts.len(),
+            skipped.join(", ")
+        ));
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ini::Ini;
+    use std::fs;
+    use crate::portal::{Account, Role};
+    use crate::test_support::FakeBackend;
+
+    fn account(account_id: &str, account_name: &str) -> Account {
+        Account {
+            account_id: account_id.to_string(),
+            account_name: account_name.to_string(),
+        }
+    }
+
+    fn role(account_id: &str, role_name: &str) -> Role {
+        Role {
+            account_id: account_id.to_string(),
+            role_name: role_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_import_profiles() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            "# Hand-written, keep this\n[sso-session acme]\nsso_start_url = https://acme.awsapps.com/start\nsso_region = eu-west-1\n\n\
+             [profile acme-Dev_Account-Admin]\nsso_session = acme\nsso_account_id = 111111111111\nsso_role_name = Admin\n",
+        )
+        .unwrap();
+
+        let backend = FakeBackend {
+            accounts: vec![account("111111111111", "Dev-Account"), account("222222222222", "Prod Account")],
+            roles: vec![
+                role("111111111111", "Admin"),
+                role("111111111111", "read-only"),
+                role("222222222222", "Admin"),
+            ],
+            ..Default::default()
+        };
+        let original = fs::read_to_string(&config_path).unwrap();
+        let backups = Backups::new(temp_dir.path().join("backups"), 5);
+        import_profiles(&backend, "acme", &config_path, &backups).unwrap();
+        assert_eq!(fs::read_to_string(&backups.list()[0].path).unwrap(), original);
+
+        assert_eq!(
+            backend.calls(),
+            vec!["login acme", "list_accounts", "list_roles 111111111111", "list_roles 222222222222"]
+        );
+        assert!(fs::read_to_string(&config_path).unwrap().starts_with("# Hand-written, keep this\n"));
+        let config = Ini::load_from_file(&config_path).unwrap();
+        let imported: Vec<&str> = config
+            .sections()
+            .flatten()
+            .filter(|name| name.starts_with("profile "))
+            .collect();
+        assert_eq!(
+            imported,
+            vec!["profile acme-Dev_Account-Admin", "profile acme-Dev_Account-read_only", "profile acme-ProdAccount-Admin"]
+        );
+        let prod = config.section(Some("profile acme-ProdAccount-Admin")).unwrap();
+        assert_eq!(prod.get("sso_account_id"), Some("222222222222"));
+        assert_eq!(prod.get("region"), Some("eu-west-1"));
+    }
+
+    #[test]
+    fn test_import_skips_accounts_without_roles_access() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        fs::write(
+            &config_path,
+            "[sso-session acme]\nsso_start_url = https://acme.awsapps.com/start\nsso_region = eu-west-1\n",
+        )
+        .unwrap();
+
+        let backend = FakeBackend {
+            accounts: vec![account("111111111111", "Dev"), account("222222222222", "Prod")],
+            roles: vec![role("111111111111", "Admin"), role("222222222222", "Admin")],
+            inaccessible_accounts: vec!["111111111111".to_string()],
+            ..Default::default()
+        };
+        let backups = Backups::new(temp_dir.path().join("backups"), 5);
+        let result = import_profiles(&backend, "acme", &config_path, &backups);
+        assert_eq!(result.unwrap_err(), "Couldn't list roles for 1 of 2 accounts, skipped 111111111111");
+
+        // The accounts that worked were still imported
+        let contents = fs::read_to_string(&config_path).unwrap();
+        assert!(contents.contains("[profile acme-Prod-Admin]"), "{}", contents);
+        assert!(!contents.contains("[profile acme-Dev-Admin]"), "{}", contents);
+    }
+
+    #[test]
+    fn test_import_requires_sso_session() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config");
+        fs::write(&config_path, "[profile other]\nregion = us-east-1\n").unwrap();
+
+        let backend = FakeBackend::default();
+        let backups = Backups::new(temp_dir.path().join("backups"), 5);
+        let result = import_profiles(&backend, "acme", &config_path, &backups);
+        assert_eq!(result.unwrap_err(), "SSO region not found in config");
+        assert!(backend.calls().is_empty());
+    }
+}
@@ -1,17 +1,40 @@
+mod atomic_file;
 mod aws;
+mod backend;
+mod backup;
+mod browser;
 mod config;
+mod config_editor;
+mod container;
+mod credential_cache;
+mod portal;
 mod profile;
+mod shell;
+mod status;
 mod ui;
 mod import;
+mod oidc;
+mod sso_cache;
 mod tui_tree;
+#[cfg(test)]
+mod test_support;
 
+use backend::{Backend, CliBackend, NativeBackend};
+use backup::Backups;
 use clap::Parser;
-use dirs::home_dir;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use config::{load_recent_profiles, load_settings, save_recent_profile};
-use profile::{load_profiles, select_filtered_values, select_unique_values};
-use ui::skim_pick;
+use config::{
+    config_sources, load_recent_profiles, load_settings, resolve_aws_config_path,
+    save_recent_profile,
+};
+use profile::{
+    load_profiles_with_diagnostics, select_by_levels, Aliases, LoadReport, Naming, Severity,
+};
+use profile::Profile;
+use shell::{Shell, ShellFormat};
+use ui::{skim_pick, skim_pick_many};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -23,7 +46,7 @@ use ui::skim_pick;
 struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
-    /// Path to AWS config
+    /// Path to AWS config [default: $AWS_CONFIG_FILE, then aws_config_path from settings, then ~/.aws/config]
     #[arg(long, global = true)]
     aws_config_path: Option<PathBuf>,
 }
@@ -34,10 +57,30 @@ enum Commands {
     Auth(AuthArgs),
     /// Import profiles from SSO session
     Import(ImportArgs),
+    /// Report config sections that are skipped or invalid
+    Lint,
+    /// Copy a profile's settings into the [default] section
+    SetDefault(SetDefaultArgs),
+    /// Run a command, or a shell, with the selected profile
+    Exec(ExecArgs),
+    /// Print role credentials for a profile as environment variables
+    Env(EnvArgs),
+    /// Print role credentials as JSON for a profile's credential_process setting
+    CredentialProcess(CredentialProcessArgs),
+    /// Print a shell function that sets AWS_PROFILE in the calling shell
+    ShellInit(ShellInitArgs),
+    /// End SSO sessions by removing their cached tokens and credentials
+    Logout(LogoutArgs),
+    /// Show every SSO session with its cached token's expiry
+    Status(StatusArgs),
+    /// List or restore the backups taken before the AWS config is changed
+    #[command(subcommand)]
+    Backup(BackupCommand),
 }
 
-#[derive(Parser, Debug)]
-struct AuthArgs {
+/// Flags that control how a profile is picked, shared by commands that select one.
+#[derive(Parser, Debug, Default)]
+struct SelectionArgs {
     /// Optional client to skip selection
     #[arg(long)]
     client: Option<String>,
@@ -47,97 +90,369 @@ struct AuthArgs {
     /// Optional role to skip selection
     #[arg(long)]
     role: Option<String>,
+    /// Skip selection for any naming level, e.g. --select env=prod
+    #[arg(long, value_name = "LEVEL=VALUE")]
+    select: Vec<String>,
     /// If set, use a unified picker instead of step-by-step
     #[arg(long)]
     unified: bool,
     /// If set, use step-by-step mode (overrides config unified_mode)
     #[arg(long)]
     step_by_step: bool,
-    /// Set the selected profile as the default AWS profile
-    #[arg(long)]
-    set_default: bool,
-    /// List all profiles without selection
-    #[arg(long)]
-    list: bool,
     /// Show recently used profiles first
     #[arg(long)]
     recent: bool,
+    /// Use tui tree widget for selection
+    #[arg(long)]
+    tree: bool,
+}
+
+/// Flags that control how the selected profile is logged in.
+#[derive(Parser, Debug, Default)]
+struct LoginArgs {
     /// Force reauthentication even if session is valid
     #[arg(long)]
     force_reauth: bool,
+    /// Log in with the SSO OIDC device flow instead of the aws CLI
+    #[arg(long)]
+    native_login: bool,
+    /// Also verify a cached session with `aws sts get-caller-identity`
+    #[arg(long)]
+    deep_check: bool,
+}
+
+#[derive(Parser, Debug)]
+struct AuthArgs {
+    #[command(flatten)]
+    selection: SelectionArgs,
+    #[command(flatten)]
+    login: LoginArgs,
+    /// Set the selected profile as the default AWS profile
+    #[arg(long)]
+    set_default: bool,
+    /// List all profiles without selection
+    #[arg(long)]
+    list: bool,
     /// Open AWS console in browser instead of logging in via CLI
     #[arg(long)]
     console: bool,
-    /// Use tui tree widget for selection
+    /// Console service to open, e.g. s3 or cloudwatch
+    #[arg(long, requires = "console")]
+    service: Option<String>,
+    /// Console region [default: the profile's region]
+    #[arg(long, requires = "console")]
+    region: Option<String>,
+    /// Console URL or path to open, e.g. /s3/buckets
+    #[arg(long, requires = "console")]
+    destination: Option<String>,
+    /// Open the console in the client's Firefox container
+    #[arg(long, requires = "console")]
+    container: bool,
+    /// Write the selected profile and its region to FILE, one per line. Not
+    /// written with --console
+    #[arg(long, value_name = "FILE")]
+    print_profile: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+struct ExecArgs {
+    #[command(flatten)]
+    selection: SelectionArgs,
+    #[command(flatten)]
+    login: LoginArgs,
+    /// Command to run, $SHELL if omitted
+    #[arg(last = true)]
+    command: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+struct EnvArgs {
+    #[command(flatten)]
+    selection: SelectionArgs,
+    /// Syntax of the printed variables
+    #[arg(long, value_enum, default_value = "bash")]
+    format: ShellFormat,
+    /// Get credentials from the SSO portal API instead of the aws CLI
     #[arg(long)]
-    tree: bool,
+    native: bool,
+}
+
+#[derive(Parser, Debug)]
+struct CredentialProcessArgs {
+    /// Profile to get credentials for
+    #[arg(long)]
+    profile: String,
+    /// Get credentials from the SSO portal API instead of the aws CLI
+    #[arg(long)]
+    native: bool,
+}
+
+#[derive(Parser, Debug)]
+struct ShellInitArgs {
+    /// Shell to write the function for
+    #[arg(value_enum)]
+    shell: Shell,
+    /// Name of the shell function
+    #[arg(long, default_value = "awsnav")]
+    name: String,
+}
+
+#[derive(Parser, Debug)]
+struct LogoutArgs {
+    /// sso-session names, or start URLs for legacy profiles [default: pick interactively]
+    sessions: Vec<String>,
+    /// Log out of every sso-session
+    #[arg(long, conflicts_with = "sessions")]
+    all: bool,
+    /// Run `aws sso logout` instead of deleting the token cache files. It
+    /// signs out of every session, so it needs --all
+    #[arg(long)]
+    aws_cli: bool,
+    /// Also remove a [default] section that uses one of the sessions
+    #[arg(long)]
+    clear_default: bool,
+}
+
+#[derive(Parser, Debug)]
+struct StatusArgs {
+    /// Print JSON instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum BackupCommand {
+    /// List backups, newest first
+    List,
+    /// Show what a backup would change and restore it
+    Restore(RestoreArgs),
+}
+
+#[derive(Parser, Debug)]
+struct RestoreArgs {
+    /// Backup id, as shown by `backup list`
+    id: String,
+    /// Restore without asking for confirmation
+    #[arg(long, short)]
+    yes: bool,
+    /// Restore a backup of another file than the current AWS config
+    #[arg(long)]
+    force: bool,
 }
 
 #[derive(Parser, Debug)]
 struct ImportArgs {
     /// SSO session name to import profiles from
     sso_session: String,
+    /// Log in with the SSO OIDC device flow instead of the aws CLI
+    #[arg(long)]
+    native_login: bool,
+}
+
+#[derive(Parser, Debug)]
+struct SetDefaultArgs {
+    /// Profile to use as the default
+    profile: String,
 }
 
 fn main() {
     let args = Args::parse();
-    let config_path = args
-        .aws_config_path
-        .unwrap_or_else(|| home_dir().unwrap().join(".aws").join("config"));
-    
+    let settings = load_settings();
+    let config_path = resolve_aws_config_path(args.aws_config_path, &settings);
+
     match args.command.unwrap_or(Commands::Auth(AuthArgs {
-        client: None,
-        account: None,
-        role: None,
-        unified: false,
-        step_by_step: false,
+        selection: SelectionArgs::default(),
+        login: LoginArgs::default(),
         set_default: false,
         list: false,
-        recent: false,
-        force_reauth: false,
         console: false,
-        tree: false,
+        service: None,
+        region: None,
+        destination: None,
+        container: false,
+        print_profile: None,
     })) {
         Commands::Import(import_args) => {
-            if let Err(e) = import::import_profiles(&import_args.sso_session, &config_path) {
+            let backend = make_backend(import_args.native_login, &settings);
+            if let Err(e) =
+                import::import_profiles(
+                    backend.as_ref(),
+                    &import_args.sso_session,
+                    &config_path,
+                    &Backups::from_settings(&settings),
+                )
+            {
                 eprintln!("Import failed: {}", e);
                 std::process::exit(1);
             }
             println!("Import completed successfully");
-            return;
         }
         Commands::Auth(auth_args) => {
-            run_auth(auth_args, config_path);
+            let backend = make_backend(auth_args.login.native_login, &settings);
+            run_auth(auth_args, backend.as_ref(), config_path, settings);
+        }
+        Commands::Exec(exec_args) => {
+            let backend = make_backend(exec_args.login.native_login, &settings);
+            run_exec(exec_args, backend.as_ref(), config_path, settings);
+        }
+        Commands::Env(env_args) => {
+            let backend = make_backend(env_args.native, &settings);
+            run_env(env_args, backend.as_ref(), config_path, settings);
+        }
+        Commands::CredentialProcess(credential_process_args) => {
+            let backend = make_backend(credential_process_args.native, &settings);
+            run_credential_process(credential_process_args, backend.as_ref(), config_path, settings);
+        }
+        Commands::ShellInit(shell_init_args) => {
+            print!("{}", shell::wrapper_function(shell_init_args.shell, &shell_init_args.name));
+        }
+        Commands::Logout(logout_args) => {
+            run_logout(logout_args, config_path, settings);
+        }
+        Commands::Status(status_args) => {
+            let naming = load_naming(&settings);
+            let profiles = load_report(&config_path, &settings, &naming).profiles;
+            let statuses = status::session_statuses(
+                &profile::sso_logins(&profiles),
+                &sso_cache::cache_dir(),
+                chrono::Utc::now(),
+            );
+            if status_args.json {
+                println!("{}", serde_json::to_string_pretty(&statuses).unwrap());
+            } else {
+                print!("{}", status::render_table(&statuses));
+            }
+        }
+        Commands::Lint => {
+            run_lint(config_path, &settings);
+        }
+        Commands::SetDefault(set_default_args) => {
+            let naming = load_naming(&settings);
+            let profiles = load_report(&config_path, &settings, &naming).profiles;
+            let Some(profile) = profiles.iter().find(|p| p.name == set_default_args.profile)
+            else {
+                eprintln!("Profile {} not found", set_default_args.profile);
+                std::process::exit(1);
+            };
+            let backups = Backups::from_settings(&settings);
+            if let Err(e) = aws::set_default_profile(profile, &config_path, &backups) {
+                eprintln!("Failed to set default profile: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Backup(backup_command) => {
+            run_backup(backup_command, config_path, settings);
         }
     }
 }
 
-fn run_auth(args: AuthArgs, config_path: PathBuf) {
-    let mut profiles = load_profiles(&config_path);
+fn load_naming(settings: &config::Settings) -> Naming {
+    Naming::from_settings(settings.naming.as_ref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-    if profiles.is_empty() {
-        eprintln!("No profiles found");
+/// Loads profiles from the AWS config and every extra config source.
+fn load_report(config_path: &Path, settings: &config::Settings, naming: &Naming) -> LoadReport {
+    let sources = config_sources(config_path, settings);
+    load_profiles_with_diagnostics(&sources, naming).unwrap_or_else(|e| {
+        eprintln!("{}", e);
         std::process::exit(1);
+    })
+}
+
+fn run_lint(config_path: PathBuf, settings: &config::Settings) {
+    let naming = load_naming(settings);
+    let report = load_report(&config_path, settings, &naming);
+
+    for diagnostic in &report.diagnostics {
+        let level = match diagnostic.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if diagnostic.section.is_empty() {
+            println!("{}: {} ({})", level, diagnostic.message, diagnostic.file.display());
+        } else {
+            println!(
+                "{}: [{}] {} ({})",
+                level,
+                diagnostic.section,
+                diagnostic.message,
+                diagnostic.file.display()
+            );
+        }
     }
 
-    let settings = load_settings();
+    let errors = report
+        .diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    let warnings = report.diagnostics.len() - errors;
+    println!(
+        "{} profiles loaded, {} errors, {} warnings",
+        report.profiles.len(),
+        errors,
+        warnings
+    );
 
-    let mut chosen_client = args.client.or(settings.default_client);
-    let mut chosen_account = args.account.or(settings.default_account);
-    let mut chosen_role = args.role.or(settings.default_role);
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+}
 
-    let unified_mode = if args.step_by_step {
-        false
-    } else {
-        args.unified || settings.unified_mode.unwrap_or_default()
-    };
-    let tree_mode = args.tree || settings.tree.unwrap_or_default();
-    let set_default = args.set_default || settings.set_default.unwrap_or_default();
-    let list = args.list || settings.list.unwrap_or_default();
-    let recent = args.recent || settings.recent.unwrap_or_default();
-    let force_reauth = args.force_reauth || settings.force_reauth.unwrap_or_default();
-    let check_session = settings.check_session.unwrap_or(true);
+/// Applies `--select LEVEL=VALUE` arguments on top of the preselected levels.
+fn apply_level_selections(
+    mut preselected: Vec<Option<String>>,
+    naming: &Naming,
+    selections: &[String],
+) -> Vec<Option<String>> {
+    for selection in selections {
+        let Some((level, value)) = selection.split_once('=') else {
+            eprintln!("Invalid --select {}, expected LEVEL=VALUE", selection);
+            std::process::exit(1);
+        };
+        let Some(index) = naming.levels.iter().position(|l| l == level) else {
+            eprintln!(
+                "Unknown level {}, expected one of: {}",
+                level,
+                naming.levels.join(", ")
+            );
+            std::process::exit(1);
+        };
+        preselected[index] = Some(value.to_string());
+    }
+    preselected
+}
+
+/// Profiles to pick from, with the naming and aliases to show them with.
+struct Candidates {
+    naming: Naming,
+    aliases: Aliases,
+    /// Every loaded profile, including hidden ones.
+    all_profiles: Vec<Profile>,
+    /// Visible profiles, most recently used first with `--recent`.
+    profiles: Vec<Profile>,
+}
+
+fn load_candidates(args: &SelectionArgs, config_path: &Path, settings: &config::Settings) -> Candidates {
+    let naming = load_naming(settings);
+
+    let aliases = Aliases::from_settings(&naming, settings.aliases.as_ref()).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let all_profiles = load_report(config_path, settings, &naming).profiles;
+    let mut profiles: Vec<_> = all_profiles.iter().filter(|p| !p.hidden).cloned().collect();
+
+    if profiles.is_empty() {
+        eprintln!("No profiles found, run `aws-sso-navigator lint` to see why");
+        std::process::exit(1);
+    }
 
+    let recent = args.recent || settings.recent.unwrap_or_default();
     if recent {
         let recent = load_recent_profiles();
         profiles.sort_by(|a, b| {
@@ -147,102 +462,487 @@ fn run_auth(args: AuthArgs, config_path: PathBuf) {
         });
     }
 
-    if list {
-        for profile in &profiles {
-            println!("{}", profile.name);
+    Candidates {
+        naming,
+        aliases,
+        all_profiles,
+        profiles,
+    }
+}
+
+/// Runs the interactive selection. Returns `None` if the user quit the tree view.
+fn pick_profile<'a>(
+    args: &SelectionArgs,
+    candidates: &'a Candidates,
+    settings: &config::Settings,
+) -> Option<&'a Profile> {
+    let Candidates {
+        naming,
+        aliases,
+        profiles,
+        ..
+    } = candidates;
+
+    // These flags are shortcuts for the default levels, and would be ignored otherwise
+    for (flag, value, level) in [
+        ("--client", &args.client, "client"),
+        ("--account", &args.account, "account"),
+        ("--role", &args.role, "role"),
+    ] {
+        if value.is_some() && !naming.levels.iter().any(|l| l == level) {
+            eprintln!(
+                "{} doesn't match a naming level, use --select LEVEL=VALUE with one of: {}",
+                flag,
+                naming.levels.join(", ")
+            );
+            std::process::exit(1);
         }
-        return;
     }
 
-    if unified_mode {
-        let rows: Vec<String> = profiles
+    let preselected: Vec<Option<String>> = naming
+        .levels
+        .iter()
+        .map(|level| match level.as_str() {
+            "client" => args.client.clone().or(settings.default_client.clone()),
+            "account" => args.account.clone().or(settings.default_account.clone()),
+            "role" => args.role.clone().or(settings.default_role.clone()),
+            _ => None,
+        })
+        .collect();
+    let preselected = apply_level_selections(preselected, naming, &args.select);
+
+    let unified_mode = if args.step_by_step {
+        false
+    } else {
+        args.unified || settings.unified_mode.unwrap_or_default()
+    };
+    let tree_mode = args.tree || settings.tree.unwrap_or_default();
+
+    let selected = if unified_mode {
+        let rows: Vec<(String, &str)> = profiles
             .iter()
-            .map(|p| format!("{} | {} | {} | {}", p.client, p.account, p.role, p.name))
+            .map(|p| {
+                let path = aliases.display_path(&p.path);
+                let mut row = format!("{} | {}", path.join(" | "), p.name);
+                let annotation = p.annotation();
+                if !annotation.is_empty() {
+                    row.push_str(&format!(" | {}", annotation));
+                }
+                (row, p.name.as_str())
+            })
             .collect();
-        if let Some(choice) = skim_pick("Select Profile", rows) {
-            let parts: Vec<&str> = choice.split('|').map(|s| s.trim()).collect();
-            chosen_client = Some(parts[0].to_string());
-            chosen_account = Some(parts[1].to_string());
-            chosen_role = Some(parts[2].to_string());
-        }
+        let options = rows.iter().map(|(row, _)| row.clone()).collect();
+        let rows: HashMap<String, &str> = rows.into_iter().collect();
+        skim_pick("Select Profile", options)
+            .and_then(|choice| rows.get(&choice).map(|name| name.to_string()))
+            .ok_or_else(|| "Selection incomplete".to_string())
     } else if tree_mode {
-        match tui_tree::tui_tree_select(&profiles) {
-            Ok(Some((client, account, role))) => {
-                chosen_client = Some(client);
-                chosen_account = Some(account);
-                chosen_role = Some(role);
-            }
-            Ok(None) => return,
+        match tui_tree::tui_tree_select(profiles, aliases) {
+            Ok(Some(name)) => Ok(name),
+            Ok(None) => return None,
             Err(e) => {
                 eprintln!("TUI error: {}", e);
                 std::process::exit(1);
             }
         }
     } else {
-        if chosen_client.is_none() {
-            chosen_client = select_unique_values(&profiles, |p| p.client.clone(), "Select Client");
-        }
-        if let (Some(client), None) = (&chosen_client, &chosen_account) {
-            chosen_account = select_filtered_values(
-                &profiles,
-                |p| &p.client == client,
-                |p| p.account.clone(),
-                "Select Account",
-            );
-        }
-        if let (Some(client), Some(account), None) = (&chosen_client, &chosen_account, &chosen_role)
-        {
-            chosen_role = select_filtered_values(
-                &profiles,
-                |p| &p.client == client && &p.account == account,
-                |p| p.role.clone(),
-                "Select Role",
-            );
-        }
-    }
+        select_by_levels(profiles, &naming.levels, aliases, &preselected, skim_pick)
+            .map(|profile| profile.name.clone())
+    };
 
-    let (Some(client), Some(account), Some(role)) = (chosen_client, chosen_account, chosen_role)
-    else {
-        eprintln!("Selection incomplete");
+    let profile = match selected {
+        Ok(name) => profiles.iter().find(|p| p.name == name),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let Some(profile) = profile else {
+        eprintln!("No matching profile found");
         std::process::exit(1);
     };
+    Some(profile)
+}
 
-    let Some(profile) = profiles
-        .iter()
-        .find(|p| p.client == client && p.account == account && p.role == role)
-    else {
-        eprintln!("No matching profile found");
+/// The SSO profile that authenticates `profile`: itself, or the root of its
+/// `source_profile` chain. `None` for `credential_source` profiles.
+fn find_sso_source<'a>(profile: &Profile, all_profiles: &'a [Profile]) -> Option<&'a Profile> {
+    let sso_profile = profile.sso_profile()?;
+    let source = all_profiles.iter().find(|p| p.name == sso_profile);
+    if source.is_none() {
+        eprintln!("Profile {} not found", sso_profile);
         std::process::exit(1);
+    }
+    source
+}
+
+/// The native backend with `--native`/`--native-login` or `native_login = true`,
+/// otherwise the `aws` CLI.
+fn make_backend(native: bool, settings: &config::Settings) -> Box<dyn Backend> {
+    let browser = settings.browser.clone();
+    let oidc_endpoint = settings.sso_oidc_endpoint.clone();
+    if native || settings.native_login.unwrap_or_default() {
+        Box::new(NativeBackend {
+            browser,
+            oidc_endpoint,
+            portal_endpoint: settings.sso_portal_endpoint.clone(),
+        })
+    } else {
+        Box::new(CliBackend { browser, oidc_endpoint })
+    }
+}
+
+/// Logs in to the SSO profile behind `profile`, exiting on failure.
+fn login(
+    backend: &dyn Backend,
+    profile: &Profile,
+    all_profiles: &[Profile],
+    args: &LoginArgs,
+    settings: &config::Settings,
+) {
+    let force_reauth = args.force_reauth || settings.force_reauth.unwrap_or_default();
+    let check_session = settings.check_session.unwrap_or(true);
+    let session_check = (check_session && !force_reauth).then(|| aws::SessionCheck {
+        skew: session_skew(settings),
+        deep: args.deep_check || settings.deep_session_check.unwrap_or_default(),
+    });
+
+    if let Some(sso_source) = find_sso_source(profile, all_profiles) {
+        // Chained profiles are authenticated through their SSO source profile
+        if let Err(e) = aws::login(backend, sso_source, session_check.as_ref()) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(credential_source) = &profile.credential_source {
+        eprintln!(
+            "Profile {} uses credential_source {}, no SSO login needed",
+            profile.name, credential_source
+        );
+    }
+}
+
+fn run_auth(args: AuthArgs, backend: &dyn Backend, config_path: PathBuf, settings: config::Settings) {
+    let candidates = load_candidates(&args.selection, &config_path, &settings);
+
+    let set_default = args.set_default || settings.set_default.unwrap_or_default();
+    let list = args.list || settings.list.unwrap_or_default();
+
+    if list {
+        let profiles = &candidates.profiles;
+        let width = profiles.iter().map(|p| p.name.len()).max().unwrap_or(0);
+        for profile in profiles {
+            let line = format!(
+                "{:<width$}  {}  {:<14}  {}",
+                profile.name,
+                profile.account_id(),
+                profile.region.as_deref().unwrap_or("-"),
+                profile.annotation(),
+            );
+            println!("{}", line.trim_end());
+        }
+        return;
+    }
+
+    let Some(profile) = pick_profile(&args.selection, &candidates, &settings) else {
+        return;
     };
 
     if args.console {
-        if let Err(e) = aws::open_console(
-            &profile.sso_start_url,
-            &profile.sso_account_id,
-            &profile.sso_role_name,
-            settings.browser.as_deref(),
-        ) {
+        let target = aws::ConsoleTarget {
+            service: args.service.clone(),
+            region: args.region.clone(),
+            destination: args.destination.clone(),
+        };
+        let container = (args.container || settings.firefox_containers.unwrap_or_default())
+            .then(|| container::Container::for_profile(profile, settings.containers.as_ref()));
+        if let Err(e) =
+            aws::open_console(profile, &target, container.as_ref(), settings.browser.as_deref())
+        {
             eprintln!("{}", e);
             std::process::exit(1);
         }
     } else {
-        if let Err(e) = aws::login_to_profile(
-            &profile.name,
-            force_reauth,
-            check_session,
-            settings.browser.as_deref(),
-        ) {
-            eprintln!("{}", e);
+        login(backend, profile, &candidates.all_profiles, &args.login, &settings);
+    }
+
+    let max_recent = settings.max_recent_profiles.unwrap_or(100);
+    save_recent_profile(&profile.name, max_recent);
+
+    if set_default
+        && let Err(e) =
+            aws::set_default_profile(profile, &config_path, &Backups::from_settings(&settings))
+    {
+        eprintln!("Failed to set default profile: {}", e);
+    }
+
+    // Machine-readable output for the shell-init wrapper, which exports the
+    // profile. A console profile isn't logged in for the CLI, so it's left out.
+    if let Some(print_profile) = &args.print_profile
+        && !args.console
+    {
+        let region = profile.region.as_deref().unwrap_or_default();
+        if let Err(e) = std::fs::write(print_profile, format!("{}\n{}\n", profile.name, region)) {
+            eprintln!("Failed to write {}: {}", print_profile.display(), e);
             std::process::exit(1);
         }
     }
+}
+
+fn session_skew(settings: &config::Settings) -> chrono::Duration {
+    chrono::Duration::seconds(settings.session_skew_seconds.unwrap_or(60))
+}
+
+fn run_env(args: EnvArgs, backend: &dyn Backend, config_path: PathBuf, settings: config::Settings) {
+    let candidates = load_candidates(&args.selection, &config_path, &settings);
+    let Some(profile) = pick_profile(&args.selection, &candidates, &settings) else {
+        return;
+    };
+
+    let sso_source = find_sso_source(profile, &candidates.all_profiles);
+    let skew = session_skew(&settings);
+    let credentials = aws::role_credentials(backend, profile, sso_source, skew).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let mut variables = credentials.env_variables();
+    if let Some(region) = &profile.region {
+        variables.push(("AWS_REGION", region.clone()));
+        variables.push(("AWS_DEFAULT_REGION", region.clone()));
+    }
+    print!("{}", args.format.assignments(&variables));
 
     let max_recent = settings.max_recent_profiles.unwrap_or(100);
     save_recent_profile(&profile.name, max_recent);
+}
+
+/// Prints credentials in the `credential_process` format. This runs under the
+/// SDKs, so it never prompts: without a valid SSO token it fails right away.
+fn run_credential_process(
+    args: CredentialProcessArgs,
+    backend: &dyn Backend,
+    config_path: PathBuf,
+    settings: config::Settings,
+) {
+    let naming = load_naming(&settings);
+    let all_profiles = load_report(&config_path, &settings, &naming).profiles;
+    let Some(profile) = all_profiles.iter().find(|p| p.name == args.profile) else {
+        eprintln!("Profile {} not found", args.profile);
+        std::process::exit(1);
+    };
+
+    let skew = session_skew(&settings);
+    let cache_path = credential_cache::credentials_path(&credential_cache::cache_dir(), &profile.name);
+    if let Some(credentials) = credential_cache::read_credentials(&cache_path, chrono::Utc::now(), skew) {
+        println!("{}", credentials.to_process_json());
+        return;
+    }
+
+    let sso_source = find_sso_source(profile, &all_profiles);
+    let credentials = aws::role_credentials(backend, profile, sso_source, skew).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = credential_cache::write_credentials(&cache_path, &credentials) {
+        eprintln!("{}", e);
+    }
+    println!("{}", credentials.to_process_json());
+}
+
+/// Variable set in `exec` children so shell prompts can show the active profile.
+const PROFILE_MARKER_VAR: &str = "AWS_SSO_NAVIGATOR_PROFILE";
+
+fn run_exec(args: ExecArgs, backend: &dyn Backend, config_path: PathBuf, settings: config::Settings) {
+    let candidates = load_candidates(&args.selection, &config_path, &settings);
+    let Some(profile) = pick_profile(&args.selection, &candidates, &settings) else {
+        return;
+    };
+    login(backend, profile, &candidates.all_profiles, &args.login, &settings);
+
+    let max_recent = settings.max_recent_profiles.unwrap_or(100);
+    save_recent_profile(&profile.name, max_recent);
+
+    let (program, program_args) = match args.command.split_first() {
+        Some((program, program_args)) => (program.clone(), program_args),
+        None => (std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string()), &[][..]),
+    };
+
+    let config_file = aws::aws_config_file(profile).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let mut command = std::process::Command::new(&program);
+    command
+        .args(program_args)
+        .env("AWS_PROFILE", &profile.name)
+        .env("AWS_CONFIG_FILE", &config_file)
+        .env(PROFILE_MARKER_VAR, &profile.name);
+    if let Some(region) = &profile.region {
+        command.env("AWS_REGION", region);
+    }
+
+    // Nothing is left to do after the command, so it replaces the navigator.
+    // Signals like Ctrl-C and the exit status then go to and from the command
+    // as if it had been started directly.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        eprintln!("Failed to run {}: {}", program, e);
+        std::process::exit(1);
+    }
+    #[cfg(not(unix))]
+    {
+        let status = command.status().unwrap_or_else(|e| {
+            eprintln!("Failed to run {}: {}", program, e);
+            std::process::exit(1);
+        });
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
 
-    if set_default {
-        if let Err(e) = aws::set_default_profile(&profile.name, &config_path) {
-            eprintln!("Failed to set default profile: {}", e);
+fn run_logout(args: LogoutArgs, config_path: PathBuf, settings: config::Settings) {
+    // A bool flag always has a value, so clap's `requires` can't check this
+    if args.aws_cli && !args.all {
+        eprintln!("--aws-cli signs out of every session, pass --all with it");
+        std::process::exit(1);
+    }
+    let naming = load_naming(&settings);
+    let profiles = load_report(&config_path, &settings, &naming).profiles;
+    let logins = profile::sso_logins(&profiles);
+
+    let selected: Vec<&profile::SsoLogin> = if args.all {
+        logins.iter().collect()
+    } else {
+        let names = if args.sessions.is_empty() {
+            skim_pick_many("Select sso-sessions (Tab to mark)", logins.iter().map(|l| l.name.clone()).collect())
+        } else {
+            args.sessions.clone()
+        };
+        names
+            .iter()
+            .map(|name| {
+                logins.iter().find(|l| &l.name == name).unwrap_or_else(|| {
+                    eprintln!("Unknown sso-session {}", name);
+                    std::process::exit(1);
+                })
+            })
+            .collect()
+    };
+    if selected.is_empty() {
+        eprintln!("No sso-session selected");
+        std::process::exit(1);
+    }
+
+    if args.aws_cli
+        && let Err(e) = aws::cli_logout()
+    {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let sso_cache_dir = sso_cache::cache_dir();
+    let cli_cache_dir = sso_cache::cli_cache_dir();
+    let credentials_dir = credential_cache::cache_dir();
+    for login in &selected {
+        let mut files = Vec::new();
+        if !args.aws_cli {
+            files.extend(sso_cache::token_files(&sso_cache_dir, login.sso_session.as_deref(), &login.start_url));
+        }
+        for profile in profiles.iter().filter(|p| login.profiles.contains(&p.name)) {
+            files.push(credential_cache::credentials_path(&credentials_dir, &profile.name));
+            if profile.role_arn.is_none() {
+                files.push(sso_cache::cli_role_cache_path(
+                    &cli_cache_dir,
+                    login.sso_session.as_deref(),
+                    &login.start_url,
+                    &profile.sso_account_id,
+                    &profile.sso_role_name,
+                ));
+            }
+        }
+
+        let mut removed = 0;
+        for file in files.iter().filter(|f| f.exists()) {
+            match std::fs::remove_file(file) {
+                Ok(()) => removed += 1,
+                Err(e) => eprintln!("Failed to remove {}: {}", file.display(), e),
+            }
+        }
+        println!("Logged out of {} ({} cached files removed)", login.name, removed);
+    }
+
+    if args.clear_default {
+        let selected: Vec<_> = selected.into_iter().cloned().collect();
+        let backups = Backups::from_settings(&settings);
+        match aws::clear_default_profile(&config_path, &selected, &backups) {
+            Ok(true) => println!("Removed the [default] profile"),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("Failed to clear default profile: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn run_backup(command: BackupCommand, config_path: PathBuf, settings: config::Settings) {
+    let backups = Backups::from_settings(&settings);
+    match command {
+        BackupCommand::List => {
+            let list = backups.list();
+            if list.is_empty() {
+                println!("No backups in {}", backup::backup_dir().display());
+                return;
+            }
+            println!("{:<19}  {:<19}  {:<8}  FILE", "ID", "CREATED", "PROFILES");
+            for backup in list {
+                let created_at = backup.created_at.with_timezone(&chrono::Local);
+                println!(
+                    "{:<19}  {}  {:<8}  {}",
+                    backup.id,
+                    created_at.format("%Y-%m-%d %H:%M:%S"),
+                    backup.profile_count(),
+                    backup.source.display()
+                );
+            }
+        }
+        BackupCommand::Restore(args) => {
+            let result = backups.find(&args.id).and_then(|backup| {
+                if !args.force && !backup::is_backup_of(&backup, &config_path) {
+                    return Err(format!(
+                        "Backup {} is of {}, not the current AWS config {}, pass --force to restore it anyway",
+                        backup.id,
+                        backup.source.display(),
+                        config_path.display()
+                    ));
+                }
+                let diff = backup::restore_diff(&backup)?;
+                if diff.is_empty() {
+                    println!("{} already matches backup {}", backup.source.display(), backup.id);
+                    return Ok(());
+                }
+                print!("{}", diff);
+                if !args.yes {
+                    let confirmed = dialoguer::Confirm::new()
+                        .with_prompt(format!("Restore {} from backup {}?", backup.source.display(), backup.id))
+                        .default(false)
+                        .interact()
+                        .map_err(|e| format!("{}, pass --yes to restore without asking", e))?;
+                    if !confirmed {
+                        println!("Restore cancelled");
+                        return Ok(());
+                    }
+                }
+                backups.restore(&backup)?;
+                println!("Restored {} from backup {}", backup.source.display(), backup.id);
+                Ok(())
+            });
+            if let Err(e) = result {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
         }
     }
 }
@@ -0,0 +1,370 @@
+use crate::sso_cache::CachedToken;
+use chrono::{Duration as ChronoDuration, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CLIENT_NAME: &str = "aws-sso-navigator";
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+const REFRESH_TOKEN_GRANT: &str = "refresh_token";
+/// Scope the AWS CLI registers for sso-session logins, needed for refresh tokens.
+const SSO_SESSION_SCOPE: &str = "sso:account:access";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Registration {
+    client_id: String,
+    client_secret: String,
+    client_secret_expires_at: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthorization {
+    device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+    refresh_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+enum OidcError {
+    /// An OAuth error returned by the service, e.g. `authorization_pending`.
+    Service {
+        code: String,
+        description: Option<String>,
+    },
+    Other(String),
+}
+
+impl From<OidcError> for String {
+    fn from(error: OidcError) -> Self {
+        match error {
+            OidcError::Service {
+                code,
+                description: Some(description),
+            } => format!("SSO OIDC request failed: {} ({})", code, description),
+            OidcError::Service { code, .. } => format!("SSO OIDC request failed: {}", code),
+            OidcError::Other(message) => message,
+        }
+    }
+}
+
+/// The sso-session or legacy start URL to log in to.
+pub struct SsoTarget<'a> {
+    pub sso_session: Option<&'a str>,
+    pub start_url: &'a str,
+    pub region: &'a str,
+}
+
+pub struct OidcClient {
+    base_url: String,
+}
+
+impl OidcClient {
+    /// Uses `AWS_ENDPOINT_URL_SSO_OIDC`, then `endpoint`, then the regional AWS endpoint.
+    pub fn new(region: &str, endpoint: Option<&str>) -> Self {
+        let base_url = std::env::var("AWS_ENDPOINT_URL_SSO_OIDC")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .or_else(|| endpoint.map(String::from))
+            .unwrap_or_else(|| format!("https://oidc.{}.amazonaws.com", region));
+        Self::with_base_url(&base_url)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T, OidcError> {
+        let url = format!("{}{}", self.base_url, path);
+        match ureq::post(&url).send_json(body) {
+            Ok(response) => response
+                .into_json()
+                .map_err(|e| OidcError::Other(format!("Invalid response from {}: {}", url, e))),
+            Err(ureq::Error::Status(status, response)) => {
+                match response.into_json::<ErrorResponse>() {
+                    Ok(error) => Err(OidcError::Service {
+                        code: error.error,
+                        description: error.error_description,
+                    }),
+                    Err(_) => Err(OidcError::Other(format!("{} returned HTTP {}", url, status))),
+                }
+            }
+            Err(e) => Err(OidcError::Other(format!("Failed to reach {}: {}", url, e))),
+        }
+    }
+
+    fn register_client(&self, target: &SsoTarget) -> Result<Registration, OidcError> {
+        let mut body = json!({ "clientName": CLIENT_NAME, "clientType": "public" });
+        if target.sso_session.is_some() {
+            body["scopes"] = json!([SSO_SESSION_SCOPE]);
+        }
+        self.post("/client/register", body)
+    }
+
+    fn start_device_authorization(
+        &self,
+        registration: &Registration,
+        start_url: &str,
+    ) -> Result<DeviceAuthorization, OidcError> {
+        self.post(
+            "/device_authorization",
+            json!({
+                "clientId": registration.client_id,
+                "clientSecret": registration.client_secret,
+                "startUrl": start_url,
+            }),
+        )
+    }
+
+    fn create_token(
+        &self,
+        registration: &Registration,
+        device_code: &str,
+    ) -> Result<TokenResponse, OidcError> {
+        self.post(
+            "/token",
+            json!({
+                "clientId": registration.client_id,
+                "clientSecret": registration.client_secret,
+                "grantType": DEVICE_CODE_GRANT,
+                "deviceCode": device_code,
+            }),
+        )
+    }
+
+    fn refresh_access_token(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<TokenResponse, OidcError> {
+        self.post(
+            "/token",
+            json!({
+                "clientId": client_id,
+                "clientSecret": client_secret,
+                "grantType": REFRESH_TOKEN_GRANT,
+                "refreshToken": refresh_token,
+            }),
+        )
+    }
+}
+
+/// Timestamp `seconds` from now, in the format the AWS CLI writes to its caches.
+pub fn expires_in(seconds: i64) -> String {
+    (Utc::now() + ChronoDuration::seconds(seconds)).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn timestamp(unix_seconds: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(unix_seconds, 0)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Runs the device authorization flow: registers a client, lets the user approve
+/// the device code in a browser, and polls until a token is issued.
+pub fn device_login<F>(
+    client: &OidcClient,
+    target: &SsoTarget,
+    on_authorize: F,
+) -> Result<CachedToken, String>
+where
+    F: FnOnce(&DeviceAuthorization),
+{
+    let registration = client.register_client(target)?;
+    let authorization = client.start_device_authorization(&registration, target.start_url)?;
+
+    on_authorize(&authorization);
+
+    let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+    let mut interval = authorization.interval;
+
+    let token = loop {
+        if Instant::now() >= deadline {
+            return Err("Device authorization expired before it was approved".to_string());
+        }
+        thread::sleep(Duration::from_secs(interval));
+
+        match client.create_token(&registration, &authorization.device_code) {
+            Ok(token) => break token,
+            Err(OidcError::Service { code, .. }) if code == "authorization_pending" => {}
+            Err(OidcError::Service { code, .. }) if code == "slow_down" => interval += 5,
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    Ok(CachedToken {
+        start_url: target.start_url.to_string(),
+        region: target.region.to_string(),
+        access_token: token.access_token,
+        expires_at: expires_in(token.expires_in),
+        client_id: Some(registration.client_id),
+        client_secret: Some(registration.client_secret),
+        registration_expires_at: timestamp(registration.client_secret_expires_at),
+        refresh_token: token.refresh_token,
+    })
+}
+
+/// Trades the refresh token of a cached token for a new access token, without
+/// the browser. The client registration is kept as it is.
+pub fn refresh_token(client: &OidcClient, token: &CachedToken) -> Result<CachedToken, String> {
+    let (Some(client_id), Some(client_secret), Some(refresh_token)) =
+        (&token.client_id, &token.client_secret, &token.refresh_token)
+    else {
+        return Err("Cached SSO token has no refresh token".to_string());
+    };
+    let response = client.refresh_access_token(client_id, client_secret, refresh_token)?;
+
+    Ok(CachedToken {
+        access_token: response.access_token,
+        expires_at: expires_in(response.expires_in),
+        // The service may rotate the refresh token
+        refresh_token: response.refresh_token.or_else(|| token.refresh_token.clone()),
+        ..token.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_server;
+
+    #[test]
+    fn test_device_login() {
+        let (base_url, server) = mock_server(vec![
+            (
+                200,
+                r#"{"clientId":"cid","clientSecret":"secret","clientIdIssuedAt":1700000000,"clientSecretExpiresAt":1900000000}"#,
+            ),
+            (
+                200,
+                r#"{"deviceCode":"dev-code","userCode":"ABCD-EFGH","verificationUri":"https://device.sso.us-east-1.amazonaws.com/","verificationUriComplete":"https://device.sso.us-east-1.amazonaws.com/?user_code=ABCD-EFGH","expiresIn":600,"interval":0}"#,
+            ),
+            (400, r#"{"error":"authorization_pending"}"#),
+            (
+                200,
+                r#"{"accessToken":"access","tokenType":"Bearer","expiresIn":28800,"refreshToken":"refresh"}"#,
+            ),
+        ]);
+
+        let client = OidcClient::with_base_url(&base_url);
+        let target = SsoTarget {
+            sso_session: Some("my-sso"),
+            start_url: "https://example.awsapps.com/start",
+            region: "us-east-1",
+        };
+        let mut user_code = None;
+        let token = device_login(&client, &target, |authorization| {
+            user_code = Some(authorization.user_code.clone());
+        })
+        .unwrap();
+
+        assert_eq!(user_code.as_deref(), Some("ABCD-EFGH"));
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(token.client_id.as_deref(), Some("cid"));
+        assert_eq!(token.start_url, "https://example.awsapps.com/start");
+        assert_eq!(token.registration_expires_at.as_deref(), Some("2030-03-17T17:46:40Z"));
+        assert!(token.expires_at.ends_with('Z'));
+
+        let requests = server.join().unwrap();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/client/register", "/device_authorization", "/token", "/token"]
+        );
+        assert!(requests.iter().all(|r| r.method == "POST"));
+        assert!(requests[0].body.contains("sso:account:access"));
+        assert!(requests[1].body.contains("\"startUrl\":\"https://example.awsapps.com/start\""));
+        assert!(requests[3].body.contains("\"deviceCode\":\"dev-code\""));
+    }
+
+    #[test]
+    fn test_device_login_denied() {
+        let (base_url, server) = mock_server(vec![
+            (
+                200,
+                r#"{"clientId":"cid","clientSecret":"secret","clientSecretExpiresAt":1900000000}"#,
+            ),
+            (
+                200,
+                r#"{"deviceCode":"dev-code","userCode":"ABCD-EFGH","verificationUri":"https://device.example","expiresIn":600,"interval":0}"#,
+            ),
+            (400, r#"{"error":"access_denied","error_description":"User denied"}"#),
+        ]);
+
+        let client = OidcClient::with_base_url(&base_url);
+        let target = SsoTarget {
+            sso_session: None,
+            start_url: "https://example.awsapps.com/start",
+            region: "us-east-1",
+        };
+        let result = device_login(&client, &target, |_| {});
+        assert_eq!(result.unwrap_err(), "SSO OIDC request failed: access_denied (User denied)");
+
+        let requests = server.join().unwrap();
+        assert!(!requests[0].body.contains("scopes"));
+    }
+
+    #[test]
+    fn test_refresh_token() {
+        let (base_url, server) = mock_server(vec![(
+            200,
+            r#"{"accessToken":"new-access","tokenType":"Bearer","expiresIn":28800}"#,
+        )]);
+
+        let cached = CachedToken {
+            start_url: "https://example.awsapps.com/start".to_string(),
+            region: "us-east-1".to_string(),
+            access_token: "old-access".to_string(),
+            expires_at: "2020-01-01T00:00:00Z".to_string(),
+            client_id: Some("cid".to_string()),
+            client_secret: Some("secret".to_string()),
+            registration_expires_at: Some("2030-03-17T17:46:40Z".to_string()),
+            refresh_token: Some("refresh".to_string()),
+        };
+        let client = OidcClient::with_base_url(&base_url);
+        let token = refresh_token(&client, &cached).unwrap();
+
+        assert_eq!(token.access_token, "new-access");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(token.client_id.as_deref(), Some("cid"));
+        assert!(token.is_valid_at(Utc::now(), ChronoDuration::hours(7)));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].path, "/token");
+        assert!(requests[0].body.contains("\"grantType\":\"refresh_token\""));
+        assert!(requests[0].body.contains("\"refreshToken\":\"refresh\""));
+
+        let without_refresh = CachedToken { refresh_token: None, ..cached };
+        assert_eq!(
+            refresh_token(&client, &without_refresh).unwrap_err(),
+            "Cached SSO token has no refresh token"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Header the SSO portal API reads the access token from.
+const BEARER_TOKEN_HEADER: &str = "x-amz-sso_bearer_token";
+
+/// Short-lived credentials for an account and role.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+    pub expiration: DateTime<Utc>,
+}
+
+impl RoleCredentials {
+    /// The environment variables SDKs and tools read credentials from.
+    pub fn env_variables(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("AWS_ACCESS_KEY_ID", self.access_key_id.clone()),
+            ("AWS_SECRET_ACCESS_KEY", self.secret_access_key.clone()),
+            ("AWS_SESSION_TOKEN", self.session_token.clone()),
+            (
+                "AWS_CREDENTIAL_EXPIRATION",
+                self.expiration.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+        ]
+    }
+}
+
+/// The `credential_process` output format, which `aws configure
+/// export-credentials --format process` also prints.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessCredentials {
+    version: u8,
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    expiration: DateTime<Utc>,
+}
+
+impl RoleCredentials {
+    pub fn to_process_json(&self) -> String {
+        let credentials = ProcessCredentials {
+            version: 1,
+            access_key_id: self.access_key_id.clone(),
+            secret_access_key: self.secret_access_key.clone(),
+            session_token: self.session_token.clone(),
+            expiration: self.expiration,
+        };
+        serde_json::to_string(&credentials).unwrap()
+    }
+
+    pub fn from_process_json(json: &[u8]) -> Result<Self, String> {
+        let credentials: ProcessCredentials = serde_json::from_slice(json)
+            .map_err(|e| format!("Failed to parse credentials: {}", e))?;
+        Ok(RoleCredentials {
+            access_key_id: credentials.access_key_id,
+            secret_access_key: credentials.secret_access_key,
+            session_token: credentials.session_token,
+            expiration: credentials.expiration,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentialsResponse {
+    role_credentials: PortalCredentials,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PortalCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: String,
+    /// Milliseconds since the epoch.
+    expiration: i64,
+}
+
+impl PortalCredentials {
+    fn into_role_credentials(self) -> Result<RoleCredentials, String> {
+        let expiration = DateTime::from_timestamp_millis(self.expiration)
+            .ok_or_else(|| format!("Invalid credential expiration {}", self.expiration))?;
+        Ok(RoleCredentials {
+            access_key_id: self.access_key_id,
+            secret_access_key: self.secret_access_key,
+            session_token: self.session_token,
+            expiration,
+        })
+    }
+}
+
+/// Parses the output of `aws sso get-role-credentials`, which has the same
+/// shape as the portal API response.
+pub fn parse_role_credentials(json: &[u8]) -> Result<RoleCredentials, String> {
+    let response: RoleCredentialsResponse = serde_json::from_slice(json)
+        .map_err(|e| format!("Failed to parse role credentials: {}", e))?;
+    response.role_credentials.into_role_credentials()
+}
+
+/// An account the user can access, as listed by the portal API and
+/// `aws sso list-accounts`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub account_id: String,
+    pub account_name: String,
+}
+
+/// A role the user can assume in an account, as listed by the portal API and
+/// `aws sso list-account-roles`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Role {
+    pub account_id: String,
+    pub role_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountList {
+    pub account_list: Vec<Account>,
+    #[serde(default)]
+    next_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleList {
+    pub role_list: Vec<Role>,
+    #[serde(default)]
+    next_token: Option<String>,
+}
+
+/// Client for the SSO portal API that hands out role credentials.
+pub struct PortalClient {
+    base_url: String,
+}
+
+impl PortalClient {
+    /// Uses `AWS_ENDPOINT_URL_SSO`, then `endpoint`, then the regional AWS endpoint.
+    pub fn new(region: &str, endpoint: Option<&str>) -> Self {
+        let base_url = std::env::var("AWS_ENDPOINT_URL_SSO")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .or_else(|| endpoint.map(String::from))
+            .unwrap_or_else(|| format!("https://portal.sso.{}.amazonaws.com", region));
+        Self::with_base_url(&base_url)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// GETs `path` with the access token and returns the response body.
+    fn get(&self, path: &str, query: &[(&str, &str)], access_token: &str, action: &str) -> Result<String, String> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut request = ureq::get(&url).set(BEARER_TOKEN_HEADER, access_token);
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+        let response = request.call().map_err(|e| match e {
+            ureq::Error::Status(401, _) => "SSO token was rejected, log in again".to_string(),
+            ureq::Error::Status(status, _) => format!("Failed to {}: HTTP {}", action, status),
+            e => format!("Failed to reach {}: {}", url, e),
+        })?;
+        response
+            .into_string()
+            .map_err(|e| format!("Invalid response from {}: {}", url, e))
+    }
+
+    pub fn role_credentials(
+        &self,
+        access_token: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<RoleCredentials, String> {
+        let body = self.get(
+            "/federation/credentials",
+            &[("account_id", account_id), ("role_name", role_name)],
+            access_token,
+            "get role credentials",
+        )?;
+        parse_role_credentials(body.as_bytes())
+    }
+
+    /// Lists every account the token can access, following pagination.
+    pub fn list_accounts(&self, access_token: &str) -> Result<Vec<Account>, String> {
+        let mut accounts = Vec::new();
+        let mut next_token = None;
+        loop {
+            let mut query = vec![("max_result", "100")];
+            if let Some(token) = next_token.as_deref() {
+                query.push(("next_token", token));
+            }
+            let body = self.get("/assignment/accounts", &query, access_token, "list accounts")?;
+            let page: AccountList = serde_json::from_str(&body)
+                .map_err(|e| format!("Failed to parse accounts: {}", e))?;
+            accounts.extend(page.account_list);
+            match page.next_token {
+                Some(token) => next_token = Some(token),
+                None => return Ok(accounts),
+            }
+        }
+    }
+
+    /// Lists the roles the token can assume in `account_id`, following pagination.
+    pub fn list_roles(&self, access_token: &str, account_id: &str) -> Result<Vec<Role>, String> {
+        let mut roles = Vec::new();
+        let mut next_token = None;
+        loop {
+            let mut query = vec![("account_id", account_id), ("max_result", "100")];
+            if let Some(token) = next_token.as_deref() {
+                query.push(("next_token", token));
+            }
+            let body = self.get("/assignment/roles", &query, access_token, "list roles")?;
+            let page: RoleList = serde_json::from_str(&body)
+                .map_err(|e| format!("Failed to parse roles: {}", e))?;
+            roles.extend(page.role_list);
+            match page.next_token {
+                Some(token) => next_token = Some(token),
+                None => return Ok(roles),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_server;
+
+    #[test]
+    fn test_role_credentials() {
+        let (base_url, server) = mock_server(vec![(
+            200,
+            r#"{"roleCredentials":{"accessKeyId":"AKIA","secretAccessKey":"secret","sessionToken":"session","expiration":1900000000000}}"#,
+        )]);
+
+        let client = PortalClient::with_base_url(&base_url);
+        let credentials = client.role_credentials("token", "123456789012", "Admin").unwrap();
+        assert_eq!(credentials.access_key_id, "AKIA");
+        assert_eq!(credentials.session_token, "session");
+        assert_eq!(credentials.expiration.to_rfc3339(), "2030-03-17T17:46:40+00:00");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(
+            requests[0].path,
+            "/federation/credentials?account_id=123456789012&role_name=Admin"
+        );
+    }
+
+    #[test]
+    fn test_process_json_round_trip() {
+        let credentials = parse_role_credentials(
+            br#"{"roleCredentials":{"accessKeyId":"AKIA","secretAccessKey":"secret","sessionToken":"session","expiration":1900000000000}}"#,
+        )
+        .unwrap();
+
+        let json = credentials.to_process_json();
+        assert_eq!(
+            json,
+            r#"{"Version":1,"AccessKeyId":"AKIA","SecretAccessKey":"secret","SessionToken":"session","Expiration":"2030-03-17T17:46:40Z"}"#
+        );
+        assert_eq!(RoleCredentials::from_process_json(json.as_bytes()).unwrap(), credentials);
+    }
+
+    #[test]
+    fn test_role_credentials_rejected_token() {
+        let (base_url, _server) = mock_server(vec![(401, r#"{"message":"Session token not found or invalid"}"#)]);
+
+        let client = PortalClient::with_base_url(&base_url);
+        let result = client.role_credentials("expired", "123456789012", "Admin");
+        assert_eq!(result.unwrap_err(), "SSO token was rejected, log in again");
+    }
+
+    #[test]
+    fn test_list_accounts_follows_pagination() {
+        let (base_url, server) = mock_server(vec![
+            (
+                200,
+                r#"{"accountList":[{"accountId":"111111111111","accountName":"Dev","emailAddress":"dev@example.com"}],"nextToken":"page-2"}"#,
+            ),
+            (200, r#"{"accountList":[{"accountId":"222222222222","accountName":"Prod"}]}"#),
+        ]);
+
+        let client = PortalClient::with_base_url(&base_url);
+        let accounts = client.list_accounts("token").unwrap();
+        assert_eq!(
+            accounts.iter().map(|a| a.account_name.as_str()).collect::<Vec<_>>(),
+            vec!["Dev", "Prod"]
+        );
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].path, "/assignment/accounts?max_result=100");
+        assert_eq!(requests[1].path, "/assignment/accounts?max_result=100&next_token=page-2");
+    }
+
+    #[test]
+    fn test_list_roles() {
+        let (base_url, server) = mock_server(vec![(
+            200,
+            r#"{"roleList":[{"roleName":"Admin","accountId":"111111111111"},{"roleName":"ReadOnly","accountId":"111111111111"}]}"#,
+        )]);
+
+        let client = PortalClient::with_base_url(&base_url);
+        let roles = client.list_roles("token", "111111111111").unwrap();
+        assert_eq!(roles[1].role_name, "ReadOnly");
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].path, "/assignment/roles?account_id=111111111111&max_result=100");
+    }
+}
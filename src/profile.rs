@@ -1,94 +1,789 @@
+use crate::config::NamingSettings;
 use ini::Ini;
-use std::collections::BTreeSet;
-use std::path::PathBuf;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Profile {
     pub name: String,
-    pub client: String,
-    pub account: String,
-    pub role: String,
-    //pub sso_session: String,
+    /// Hierarchy segments from the naming scheme, e.g. client, account, role.
+    pub path: Vec<String>,
+    /// `None` for legacy profiles that carry `sso_start_url` directly.
+    pub sso_session: Option<String>,
     pub sso_account_id: String,
     pub sso_role_name: String,
     pub sso_start_url: String,
+    pub sso_region: Option<String>,
+    pub region: Option<String>,
+    pub output: Option<String>,
+    /// Role assumed on top of the source credentials, for chained profiles.
+    pub role_arn: Option<String>,
+    /// Source profiles from the immediate `source_profile` down to the SSO profile.
+    pub chain: Vec<String>,
+    pub credential_source: Option<String>,
+    /// Keys from the profile section that don't have a field of their own.
+    pub properties: BTreeMap<String, String>,
+    /// Config file the profile was loaded from.
+    pub source_file: PathBuf,
+    /// Other sections the profile needs, its source profiles and
+    /// `[sso-session]`, with the file each one is defined in.
+    pub referenced_sections: Vec<(String, PathBuf)>,
+    /// From `navigator_tags`, a comma separated list.
+    pub tags: Vec<String>,
+    /// From `navigator_description`.
+    pub description: Option<String>,
+    /// From `navigator_hidden`; hidden profiles are left out of the pickers.
+    pub hidden: bool,
+}
+
+impl Profile {
+    /// Name of the SSO profile that has to be logged in to use this profile.
+    pub fn sso_profile(&self) -> Option<&str> {
+        match (&self.role_arn, &self.credential_source) {
+            // The chain ends in a credential_source, not an SSO profile
+            (Some(_), Some(_)) => None,
+            (Some(_), None) => self.chain.last().map(String::as_str),
+            (None, _) => Some(&self.name),
+        }
+    }
+
+    /// Account the profile's credentials belong to, the assumed role's account for chained profiles.
+    pub fn account_id(&self) -> String {
+        self.role_arn
+            .as_deref()
+            .and_then(parse_role_arn)
+            .map(|(account_id, _)| account_id)
+            .unwrap_or_else(|| self.sso_account_id.clone())
+    }
+
+    /// Tags and description on one line, e.g. `#prod #pci Payments account`.
+    pub fn annotation(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| format!("#{}", tag))
+            .chain(self.description.clone())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Label and value pairs describing the profile, for detail views.
+    pub fn details(&self) -> Vec<(String, String)> {
+        let mut details = vec![
+            ("Profile".to_string(), self.name.clone()),
+            ("Account".to_string(), self.account_id()),
+        ];
+        let mut push = |label: &str, value: Option<&str>| {
+            if let Some(value) = value.filter(|value| !value.is_empty()) {
+                details.push((label.to_string(), value.to_string()));
+            }
+        };
+        push("Description", self.description.as_deref());
+        push("Tags", Some(&self.tags.join(", ")));
+        push("Role ARN", self.role_arn.as_deref());
+        push("SSO role", Some(&self.sso_role_name));
+        push("SSO account", Some(&self.sso_account_id));
+        push("SSO session", self.sso_session.as_deref());
+        push("SSO start URL", Some(&self.sso_start_url));
+        push("SSO region", self.sso_region.as_deref());
+        push("Region", self.region.as_deref());
+        push("Output", self.output.as_deref());
+        push("Credential source", self.credential_source.as_deref());
+        if !self.chain.is_empty() {
+            push("Source chain", Some(&self.chain.join(" ⇐ ")));
+        }
+        for (key, value) in &self.properties {
+            push(key, Some(value));
+        }
+        push("Config file", Some(&self.source_file.display().to_string()));
+        details
+    }
 }
 
-pub fn load_profiles(config_path: &PathBuf) -> Vec<Profile> {
-    let Ok(ini) = Ini::load_from_file(config_path) else {
-        return Vec::new();
-    };
+const DEFAULT_SEPARATOR: &str = "-";
+const DEFAULT_FALLBACK: &str = "other";
+const DEFAULT_LEVELS: [&str; 3] = ["client", "account", "role"];
+
+/// How a profile name is split into hierarchy levels.
+pub enum NamingScheme {
+    /// Split on a separator; the last level takes everything that's left.
+    Separator(String),
+    /// Regex with a named group per level.
+    Pattern(Regex),
+}
 
-    let mut profiles = Vec::new();
+pub struct Naming {
+    pub scheme: NamingScheme,
+    /// Level names from the top of the hierarchy down, e.g. client, account, role.
+    pub levels: Vec<String>,
+    /// Top-level bucket for profiles whose name doesn't match the scheme.
+    pub fallback: String,
+}
 
-    for (section_name, properties) in ini.iter() {
-        if let Some(section_name) = section_name {
-            if let Some(profile_name) = section_name.strip_prefix("profile ") {
-                if let Some(profile) = parse_profile(profile_name, properties, &ini) {
-                    profiles.push(profile);
+impl Default for Naming {
+    fn default() -> Self {
+        Self {
+            scheme: NamingScheme::Separator(DEFAULT_SEPARATOR.to_string()),
+            levels: DEFAULT_LEVELS.iter().map(|level| level.to_string()).collect(),
+            fallback: DEFAULT_FALLBACK.to_string(),
+        }
+    }
+}
+
+impl Naming {
+    pub fn from_settings(settings: Option<&NamingSettings>) -> Result<Self, String> {
+        let Some(settings) = settings else {
+            return Ok(Self::default());
+        };
+
+        let levels = settings
+            .levels
+            .clone()
+            .unwrap_or_else(|| Self::default().levels);
+        if levels.is_empty() {
+            return Err("naming: levels must not be empty".to_string());
+        }
+
+        let scheme = match (&settings.pattern, &settings.separator) {
+            (Some(_), Some(_)) => {
+                return Err("naming: set either `pattern` or `separator`, not both".to_string());
+            }
+            (Some(pattern), None) => {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| format!("naming: invalid pattern: {}", e))?;
+                for group in &levels {
+                    if !regex.capture_names().any(|name| name == Some(group.as_str())) {
+                        return Err(format!("naming: pattern is missing the `{}` group", group));
+                    }
                 }
+                NamingScheme::Pattern(regex)
+            }
+            (None, Some(separator)) if separator.is_empty() => {
+                return Err("naming: separator must not be empty".to_string());
+            }
+            (None, Some(separator)) => NamingScheme::Separator(separator.clone()),
+            (None, None) => NamingScheme::Separator(DEFAULT_SEPARATOR.to_string()),
+        };
+
+        Ok(Self {
+            scheme,
+            levels,
+            fallback: settings
+                .fallback
+                .clone()
+                .unwrap_or_else(|| DEFAULT_FALLBACK.to_string()),
+        })
+    }
+
+    /// Splits a profile name into one segment per level, or `None` if it doesn't match.
+    pub fn split(&self, name: &str) -> Option<Vec<String>> {
+        let depth = self.levels.len();
+        match &self.scheme {
+            NamingScheme::Separator(separator) => {
+                let parts: Vec<&str> = name.split(separator.as_str()).collect();
+                if parts.len() < depth || parts.iter().any(|part| part.is_empty()) {
+                    return None;
+                }
+                let mut path: Vec<String> =
+                    parts[..depth - 1].iter().map(|part| part.to_string()).collect();
+                path.push(parts[depth - 1..].join(separator));
+                Some(path)
+            }
+            NamingScheme::Pattern(regex) => {
+                let captures = regex.captures(name)?;
+                self.levels
+                    .iter()
+                    .map(|level| {
+                        captures
+                            .name(level)
+                            .map(|m| m.as_str().to_string())
+                            .filter(|value| !value.is_empty())
+                    })
+                    .collect()
             }
         }
     }
 
-    profiles
+    /// Path for a profile name. Names that don't follow the naming scheme are
+    /// grouped under the fallback bucket, with the profile name and `role` as
+    /// the last two levels, so the path is as deep as the scheme's. Levels in
+    /// between repeat the fallback; with two levels the role is left out.
+    fn path_for(&self, name: &str, role: &str) -> Vec<String> {
+        self.split(name).unwrap_or_else(|| match self.levels.len() {
+            1 => vec![name.to_string()],
+            2 => vec![self.fallback.clone(), name.to_string()],
+            depth => {
+                let mut path = vec![self.fallback.clone(); depth - 2];
+                path.push(name.to_string());
+                path.push(role.to_string());
+                path
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
 }
 
-fn parse_profile(name: &str, properties: &ini::Properties, ini: &Ini) -> Option<Profile> {
-    let parts: Vec<&str> = name.split('-').collect();
-    if parts.len() < 3
-        || !properties.contains_key("sso_session")
-        || !properties.contains_key("sso_account_id")
-        || !properties.contains_key("sso_role_name")
+/// A problem found while loading a config section.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// Empty for problems with the file itself.
+    pub section: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct LoadReport {
+    pub profiles: Vec<Profile>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LoadReport {
+    fn push(&mut self, file: &Path, section: &str, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            file: file.to_path_buf(),
+            section: section.to_string(),
+            severity,
+            message,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Parsed config files. References to other sections are resolved against the
+/// first file that defines them.
+struct ConfigFiles {
+    files: Vec<(PathBuf, Ini)>,
+}
+
+impl ConfigFiles {
+    fn section(&self, name: &str) -> Option<&ini::Properties> {
+        self.files.iter().find_map(|(_, ini)| ini.section(Some(name)))
+    }
+
+    fn section_file(&self, name: &str) -> Option<&Path> {
+        self.files
+            .iter()
+            .find(|(_, ini)| ini.section(Some(name)).is_some())
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Where the sections `profile` depends on are defined.
+    fn referenced_sections(&self, profile: &Profile) -> Vec<(String, PathBuf)> {
+        profile
+            .chain
+            .iter()
+            .map(|name| profile_section_name(name))
+            .chain(profile.sso_session.iter().map(|session| format!("sso-session {}", session)))
+            .filter_map(|section| {
+                let file = self.section_file(&section)?.to_path_buf();
+                Some((section, file))
+            })
+            .collect()
+    }
+}
+
+/// Loads and merges profiles from all config files and records why any section
+/// was skipped or looks wrong. The first file is required, the others are optional.
+pub fn load_profiles_with_diagnostics(
+    config_paths: &[PathBuf],
+    naming: &Naming,
+) -> Result<LoadReport, String> {
+    let mut report = LoadReport::default();
+    let mut files = ConfigFiles { files: Vec::new() };
+
+    if let Some(config_path) = config_paths.first()
+        && !config_path.exists()
     {
-        return None;
+        return Err(format!("AWS config {} not found", config_path.display()));
+    }
+
+    for (index, config_path) in config_paths.iter().enumerate() {
+        match Ini::load_from_file(config_path) {
+            Ok(ini) => files.files.push((config_path.clone(), ini)),
+            Err(e) if index == 0 => {
+                return Err(format!("Failed to parse {}: {}", config_path.display(), e));
+            }
+            Err(e) => report.push(config_path, "", Severity::Error, format!("failed to load: {}", e)),
+        }
+    }
+
+    // Where each section was first defined, to report collisions between files
+    let mut defined_in: HashMap<&str, &Path> = HashMap::new();
+
+    for (config_path, ini) in &files.files {
+        for (section_name, properties) in ini.iter() {
+            let Some(section_name) = section_name else {
+                continue;
+            };
+
+            let is_session = section_name.starts_with("sso-session ");
+            if !is_session && !section_name.starts_with("profile ") {
+                continue;
+            }
+            if let Some(first) = defined_in.get(section_name) {
+                report.push(
+                    config_path,
+                    section_name,
+                    Severity::Error,
+                    format!("already defined in {}, skipped", first.display()),
+                );
+                continue;
+            }
+            defined_in.insert(section_name, config_path);
+
+            if let Some(session_name) = section_name.strip_prefix("sso-session ") {
+                if !properties.contains_key("sso_start_url") {
+                    report.push(
+                        config_path,
+                        section_name,
+                        Severity::Error,
+                        format!("sso-session {} has no sso_start_url", session_name),
+                    );
+                }
+                continue;
+            }
+
+            let Some(profile_name) = section_name.strip_prefix("profile ") else {
+                continue;
+            };
+
+            let is_chained = properties.contains_key("role_arn");
+            if !is_chained && !properties.iter().any(|(key, _)| key.starts_with("sso_")) {
+                report.push(
+                    config_path,
+                    section_name,
+                    Severity::Warning,
+                    "not an SSO profile, skipped".to_string(),
+                );
+                continue;
+            }
+
+            let profile = if is_chained {
+                parse_chained_profile(profile_name, properties, &files, naming)
+            } else {
+                parse_profile(profile_name, properties, &files, naming)
+            };
+
+            match profile {
+                Ok(profile) => {
+                    if naming.split(profile_name).is_none() {
+                        report.push(
+                            config_path,
+                            section_name,
+                            Severity::Warning,
+                            format!(
+                                "name doesn't match the naming scheme, grouped under '{}'",
+                                naming.fallback
+                            ),
+                        );
+                    }
+                    report.profiles.push(Profile {
+                        source_file: config_path.clone(),
+                        referenced_sections: files.referenced_sections(&profile),
+                        ..profile
+                    });
+                }
+                Err(reason) => report.push(config_path, section_name, Severity::Error, reason),
+            }
+        }
     }
 
-    let sso_session_name = &properties["sso_session"];
-    let sso_session_section = ini.section(Some(&format!("sso-session {}", sso_session_name)))?;
-    let sso_start_url = sso_session_section.get("sso_start_url")?;
+    check_duplicates(&mut report);
+
+    Ok(report)
+}
+
+fn check_duplicates(report: &mut LoadReport) {
+    let mut seen: HashMap<(String, String), String> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for profile in report.profiles.iter().filter(|p| p.role_arn.is_none()) {
+        let key = (profile.sso_account_id.clone(), profile.sso_role_name.clone());
+        match seen.get(&key) {
+            Some(first) => duplicates.push((
+                profile.source_file.clone(),
+                format!("profile {}", profile.name),
+                format!(
+                    "account {} and role {} are already used by profile {}",
+                    key.0, key.1, first
+                ),
+            )),
+            None => {
+                seen.insert(key, profile.name.clone());
+            }
+        }
+    }
+
+    for (file, section, message) in duplicates {
+        report.push(&file, &section, Severity::Error, message);
+    }
+}
 
-    let client = parts[0].to_string();
-    let account = parts[1].to_string();
-    let role = parts[2..].join("-");
+/// Keys that are stored in their own `Profile` fields.
+const PROFILE_KEYS: [&str; 13] = [
+    "sso_session",
+    "sso_account_id",
+    "sso_role_name",
+    "sso_start_url",
+    "sso_region",
+    "region",
+    "output",
+    "role_arn",
+    "source_profile",
+    "credential_source",
+    "navigator_tags",
+    "navigator_description",
+    "navigator_hidden",
+];
 
-    Some(Profile {
+/// The SSO part of a profile, from an SSO profile or the end of a `source_profile` chain.
+#[derive(Default)]
+struct SsoSource {
+    session: Option<String>,
+    account_id: String,
+    role_name: String,
+    start_url: String,
+    region: Option<String>,
+}
+
+fn parse_profile(
+    name: &str,
+    properties: &ini::Properties,
+    files: &ConfigFiles,
+    naming: &Naming,
+) -> Result<Profile, String> {
+    let sso = resolve_sso(properties, files)?;
+    Ok(build_profile(name, properties, naming, sso))
+}
+
+fn build_profile(name: &str, properties: &ini::Properties, naming: &Naming, sso: SsoSource) -> Profile {
+    let get = |key: &str| properties.get(key).map(String::from);
+    // Chained profiles are filed under the role they assume
+    let role = get("role_arn")
+        .as_deref()
+        .and_then(parse_role_arn)
+        .map_or_else(|| sso.role_name.clone(), |(_, role)| role);
+    Profile {
         name: name.to_string(),
-        client,
-        account,
-        role,
-        //sso_session: properties["sso_session"].to_string(),
-        sso_account_id: properties["sso_account_id"].to_string(),
-        sso_role_name: properties["sso_role_name"].to_string(),
-        sso_start_url: sso_start_url.to_string(),
+        path: naming.path_for(name, &role),
+        sso_session: sso.session,
+        sso_account_id: sso.account_id,
+        sso_role_name: sso.role_name,
+        sso_start_url: sso.start_url,
+        sso_region: sso.region,
+        region: get("region"),
+        output: get("output"),
+        role_arn: get("role_arn"),
+        chain: Vec::new(),
+        credential_source: None,
+        source_file: PathBuf::new(),
+        referenced_sections: Vec::new(),
+        tags: properties
+            .get("navigator_tags")
+            .map(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        description: get("navigator_description").filter(|description| !description.is_empty()),
+        hidden: properties
+            .get("navigator_hidden")
+            .is_some_and(|hidden| hidden.eq_ignore_ascii_case("true")),
+        properties: properties
+            .iter()
+            .filter(|(key, _)| !PROFILE_KEYS.contains(key))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    }
+}
+
+fn require<'a>(properties: &'a ini::Properties, key: &str) -> Result<&'a str, String> {
+    properties.get(key).ok_or_else(|| format!("missing {}", key))
+}
+
+/// Parses an assume-role profile, following `source_profile` down to the SSO
+/// profile it's chained from. The `sso_*` fields are taken from that SSO profile.
+fn parse_chained_profile(
+    name: &str,
+    properties: &ini::Properties,
+    files: &ConfigFiles,
+    naming: &Naming,
+) -> Result<Profile, String> {
+    let role_arn = require(properties, "role_arn")?;
+    parse_role_arn(role_arn).ok_or_else(|| format!("invalid role_arn {}", role_arn))?;
+
+    let mut chain: Vec<String> = Vec::new();
+    let mut current = properties;
+    let mut sso = SsoSource::default();
+    let mut credential_source = None;
+
+    loop {
+        if let Some(source) = current.get("source_profile") {
+            if source == name || chain.iter().any(|seen| seen == source) {
+                return Err(format!("source_profile chain loops back to {}", source));
+            }
+            chain.push(source.to_string());
+            current = profile_section(files, source)
+                .ok_or_else(|| format!("source_profile {} does not exist", source))?;
+            if current.contains_key("role_arn") {
+                continue;
+            }
+            sso = resolve_sso(current, files)
+                .map_err(|e| format!("source_profile {}: {}", source, e))?;
+        } else {
+            credential_source = Some(
+                current
+                    .get("credential_source")
+                    .ok_or("missing source_profile or credential_source")?
+                    .to_string(),
+            );
+        }
+        break;
+    }
+
+    Ok(Profile {
+        chain,
+        credential_source,
+        ..build_profile(name, properties, naming, sso)
     })
 }
 
-pub fn select_unique_values<F>(profiles: &[Profile], extractor: F, prompt: &str) -> Option<String>
-where
-    F: Fn(&Profile) -> String,
-{
-    let options: Vec<String> = profiles
+fn profile_section<'a>(files: &'a ConfigFiles, name: &str) -> Option<&'a ini::Properties> {
+    files.section(&profile_section_name(name))
+}
+
+/// `[default]` has no `profile` prefix, every other profile has.
+pub fn profile_section_name(name: &str) -> String {
+    if name == "default" {
+        name.to_string()
+    } else {
+        format!("profile {}", name)
+    }
+}
+
+/// Splits `arn:aws:iam::123456789012:role/path/Name` into (account id, role name).
+pub fn parse_role_arn(role_arn: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = role_arn.splitn(6, ':').collect();
+    if parts.len() != 6 || parts[0] != "arn" || parts[2] != "iam" {
+        return None;
+    }
+    let role_path = parts[5].strip_prefix("role/")?;
+    let role_name = role_path.rsplit('/').next()?;
+    if parts[4].is_empty() || role_name.is_empty() {
+        return None;
+    }
+    Some((parts[4].to_string(), role_name.to_string()))
+}
+
+/// Reads the SSO settings of a profile. The start URL and region come from the
+/// referenced `[sso-session]`, or directly from the profile for legacy SSO profiles.
+fn resolve_sso(properties: &ini::Properties, files: &ConfigFiles) -> Result<SsoSource, String> {
+    let account_id = require(properties, "sso_account_id")?.to_string();
+    let role_name = require(properties, "sso_role_name")?.to_string();
+
+    match properties.get("sso_session") {
+        Some(sso_session_name) => {
+            let session = files
+                .section(&format!("sso-session {}", sso_session_name))
+                .ok_or_else(|| format!("sso_session {} does not exist", sso_session_name))?;
+            let start_url = session
+                .get("sso_start_url")
+                .ok_or_else(|| format!("sso-session {} has no sso_start_url", sso_session_name))?;
+            Ok(SsoSource {
+                session: Some(sso_session_name.to_string()),
+                account_id,
+                role_name,
+                start_url: start_url.to_string(),
+                region: session.get("sso_region").map(String::from),
+            })
+        }
+        None => {
+            let start_url = require(properties, "sso_start_url")
+                .map_err(|_| "missing sso_session or sso_start_url".to_string())?;
+            Ok(SsoSource {
+                session: None,
+                account_id,
+                role_name,
+                start_url: start_url.to_string(),
+                region: properties.get("sso_region").map(String::from),
+            })
+        }
+    }
+}
+
+/// An SSO login shared by profiles: an sso-session, or a legacy start URL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SsoLogin {
+    /// The sso-session name, or the start URL for legacy profiles.
+    pub name: String,
+    pub sso_session: Option<String>,
+    pub start_url: String,
+    pub region: Option<String>,
+    /// Profiles that authenticate through this login, chained ones included.
+    pub profiles: Vec<String>,
+}
+
+/// Groups profiles by the SSO login they use, in order of first appearance.
+/// `credential_source` profiles use none and are left out.
+pub fn sso_logins(profiles: &[Profile]) -> Vec<SsoLogin> {
+    let mut logins: Vec<SsoLogin> = Vec::new();
+    for profile in profiles.iter().filter(|p| !p.sso_start_url.is_empty()) {
+        let name = profile
+            .sso_session
+            .clone()
+            .unwrap_or_else(|| profile.sso_start_url.clone());
+        match logins.iter_mut().find(|login| login.name == name) {
+            Some(login) => login.profiles.push(profile.name.clone()),
+            None => logins.push(SsoLogin {
+                name,
+                sso_session: profile.sso_session.clone(),
+                start_url: profile.sso_start_url.clone(),
+                region: profile.sso_region.clone(),
+                profiles: vec![profile.name.clone()],
+            }),
+        }
+    }
+    logins
+}
+
+/// Display names for path segments, per level, e.g. `AWSAdministratorAccess` → `admin`.
+#[derive(Default)]
+pub struct Aliases {
+    by_depth: Vec<HashMap<String, String>>,
+}
+
+impl Aliases {
+    pub fn from_settings(
+        naming: &Naming,
+        aliases: Option<&HashMap<String, HashMap<String, String>>>,
+    ) -> Result<Self, String> {
+        let mut by_depth = vec![HashMap::new(); naming.levels.len()];
+        for (level, table) in aliases.into_iter().flatten() {
+            let depth = naming
+                .levels
+                .iter()
+                .position(|l| l == level)
+                .ok_or_else(|| format!("aliases: unknown level `{}`", level))?;
+            by_depth[depth] = table.clone();
+        }
+        Ok(Self { by_depth })
+    }
+
+    pub fn display<'a>(&'a self, depth: usize, segment: &'a str) -> &'a str {
+        self.by_depth
+            .get(depth)
+            .and_then(|table| table.get(segment))
+            .map_or(segment, String::as_str)
+    }
+
+    pub fn display_path(&self, path: &[String]) -> Vec<String> {
+        path.iter()
+            .enumerate()
+            .map(|(depth, segment)| self.display(depth, segment).to_string())
+            .collect()
+    }
+}
+
+fn unique_values(profiles: &[&Profile], depth: usize, aliases: &Aliases) -> Vec<String> {
+    profiles
         .iter()
-        .map(extractor)
+        .map(|p| aliases.display(depth, &p.path[depth]))
         .collect::<BTreeSet<_>>()
         .into_iter()
-        .collect();
-    crate::ui::skim_pick(prompt, options)
+        .map(String::from)
+        .collect()
 }
 
-pub fn select_filtered_values<F, P>(
-    profiles: &[Profile],
-    filter: P,
-    extractor: F,
-    prompt: &str,
-) -> Option<String>
+/// Walks the hierarchy one level at a time. Each level uses its `preselected`
+/// value when given and otherwise asks `pick` to choose between the values left.
+/// Options are shown by alias; preselected values may be an alias or the raw
+/// segment. A value that stands for more than one segment is an error.
+pub fn select_by_levels<'a, F>(
+    profiles: &'a [Profile],
+    levels: &[String],
+    aliases: &Aliases,
+    preselected: &[Option<String>],
+    mut pick: F,
+) -> Result<&'a Profile, String>
 where
-    F: Fn(&Profile) -> String,
-    P: Fn(&Profile) -> bool,
+    F: FnMut(&str, Vec<String>) -> Option<String>,
 {
-    let filtered: Vec<_> = profiles.iter().filter(|p| filter(p)).cloned().collect();
-    select_unique_values(&filtered, extractor, prompt)
+    let mut candidates: Vec<&Profile> = profiles.iter().collect();
+    let mut depth = 0;
+
+    loop {
+        let deeper: Vec<&Profile> = candidates
+            .iter()
+            .copied()
+            .filter(|p| p.path.len() > depth)
+            .collect();
+        if deeper.is_empty() {
+            break;
+        }
+
+        let level = levels.get(depth).map(String::as_str).unwrap_or("profile");
+        let value = match preselected.get(depth).cloned().flatten() {
+            Some(value) => value,
+            None => {
+                let options = unique_values(&deeper, depth, aliases);
+                pick(&format!("Select {}", capitalize(level)), options)
+                    .ok_or("Selection incomplete")?
+            }
+        };
+
+        // The value may be a raw segment or an alias, but has to stand for one segment
+        let segments: BTreeSet<&str> = deeper
+            .iter()
+            .map(|p| p.path[depth].as_str())
+            .filter(|segment| *segment == value || aliases.display(depth, segment) == value)
+            .collect();
+        if segments.len() > 1 {
+            return Err(format!(
+                "{} {} is ambiguous, it matches {}",
+                level,
+                value,
+                segments.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        candidates = deeper
+            .into_iter()
+            .filter(|p| segments.contains(p.path[depth].as_str()))
+            .collect();
+        depth += 1;
+    }
+
+    let matches: Vec<&Profile> = candidates.into_iter().filter(|p| p.path.len() == depth).collect();
+    match matches[..] {
+        [] => Err("No matching profile found".to_string()),
+        [profile] => Ok(profile),
+        _ => Err(format!(
+            "Profiles {} have the same path, rename one of them",
+            matches.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
 
 #[cfg(test)]
@@ -96,10 +791,63 @@ mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    fn load_profiles(config_paths: &[PathBuf], naming: &Naming) -> Vec<Profile> {
+        load_profiles_with_diagnostics(config_paths, naming)
+            .unwrap()
+            .profiles
+    }
+
+    #[test]
+    fn test_sso_logins() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com")
+            .set("sso_region", "eu-west-1");
+        ini.with_section(Some("profile client-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "Admin");
+        ini.with_section(Some("profile client-prod-deploy"))
+            .set("role_arn", "arn:aws:iam::987654321098:role/Deploy")
+            .set("source_profile", "client-dev-admin");
+        ini.with_section(Some("profile legacy-dev-admin"))
+            .set("sso_start_url", "https://legacy.example.com")
+            .set("sso_region", "us-east-1")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "Admin");
+        ini.with_section(Some("profile ci-prod-deploy"))
+            .set("role_arn", "arn:aws:iam::987654321098:role/Deploy")
+            .set("credential_source", "Environment");
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        let logins = sso_logins(&profiles);
+        assert_eq!(
+            logins,
+            vec![
+                SsoLogin {
+                    name: "example".to_string(),
+                    sso_session: Some("example".to_string()),
+                    start_url: "https://example.com".to_string(),
+                    region: Some("eu-west-1".to_string()),
+                    profiles: vec!["client-dev-admin".to_string(), "client-prod-deploy".to_string()],
+                },
+                SsoLogin {
+                    name: "https://legacy.example.com".to_string(),
+                    sso_session: None,
+                    start_url: "https://legacy.example.com".to_string(),
+                    region: Some("us-east-1".to_string()),
+                    profiles: vec!["legacy-dev-admin".to_string()],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_load_profiles_empty_file() {
         let temp_file = NamedTempFile::new().unwrap();
-        let profiles = load_profiles(&temp_file.path().to_path_buf());
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
         assert!(profiles.is_empty());
     }
 
@@ -123,18 +871,14 @@ mod tests {
         
         ini.write_to_file(temp_file.path()).unwrap();
 
-        let profiles = load_profiles(&temp_file.path().to_path_buf());
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
         assert_eq!(profiles.len(), 2);
 
-        assert_eq!(profiles[0].client, "client1");
-        assert_eq!(profiles[0].account, "dev");
-        assert_eq!(profiles[0].role, "admin");
+        assert_eq!(profiles[0].path, vec!["client1", "dev", "admin"]);
         assert_eq!(profiles[0].name, "client1-dev-admin");
         assert_eq!(profiles[0].sso_start_url, "https://example.com");
 
-        assert_eq!(profiles[1].client, "client2");
-        assert_eq!(profiles[1].account, "prod");
-        assert_eq!(profiles[1].role, "readonly");
+        assert_eq!(profiles[1].path, vec!["client2", "prod", "readonly"]);
         assert_eq!(profiles[1].sso_start_url, "https://example.com");
     }
 
@@ -159,7 +903,7 @@ mod tests {
         
         ini.write_to_file(temp_file.path()).unwrap();
 
-        let profiles = load_profiles(&temp_file.path().to_path_buf());
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
         assert_eq!(profiles.len(), 1);
         assert_eq!(profiles[0].name, "valid-dev-admin");
     }
@@ -179,8 +923,651 @@ mod tests {
         
         ini.write_to_file(temp_file.path()).unwrap();
 
-        let profiles = load_profiles(&temp_file.path().to_path_buf());
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
         assert_eq!(profiles.len(), 1);
-        assert_eq!(profiles[0].role, "power-user-access");
+        assert_eq!(profiles[0].path[2], "power-user-access");
+    }
+
+    #[test]
+    fn test_load_profiles_captures_settings() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com")
+            .set("sso_region", "us-east-1");
+
+        ini.with_section(Some("profile client1-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess")
+            .set("region", "eu-west-1")
+            .set("output", "json")
+            .set("cli_pager", "");
+
+        ini.with_section(Some("profile client1-prod-deploy"))
+            .set("role_arn", "arn:aws:iam::987654321098:role/Deploy")
+            .set("source_profile", "client1-dev-admin")
+            .set("region", "eu-central-1");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        assert_eq!(profiles.len(), 2);
+
+        assert_eq!(profiles[0].sso_session.as_deref(), Some("example"));
+        assert_eq!(profiles[0].sso_region.as_deref(), Some("us-east-1"));
+        assert_eq!(profiles[0].region.as_deref(), Some("eu-west-1"));
+        assert_eq!(profiles[0].output.as_deref(), Some("json"));
+        assert_eq!(
+            profiles[0].properties,
+            BTreeMap::from([("cli_pager".to_string(), String::new())])
+        );
+        assert_eq!(profiles[0].account_id(), "123456789012");
+
+        assert_eq!(profiles[1].sso_session.as_deref(), Some("example"));
+        assert_eq!(profiles[1].sso_region.as_deref(), Some("us-east-1"));
+        assert_eq!(profiles[1].region.as_deref(), Some("eu-central-1"));
+        assert!(profiles[1].properties.is_empty());
+        assert_eq!(profiles[1].account_id(), "987654321098");
+    }
+
+    #[test]
+    fn test_load_profiles_navigator_keys() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("profile client1-prod-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess")
+            .set("navigator_tags", "prod, pci,")
+            .set("navigator_description", "Payments production")
+            .set("navigator_hidden", "false");
+
+        ini.with_section(Some("profile client1-legacy-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "AdministratorAccess")
+            .set("navigator_hidden", "TRUE");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        assert_eq!(profiles.len(), 2);
+
+        assert_eq!(profiles[0].tags, vec!["prod", "pci"]);
+        assert_eq!(profiles[0].description.as_deref(), Some("Payments production"));
+        assert!(!profiles[0].hidden);
+        assert!(profiles[0].properties.is_empty());
+        assert_eq!(profiles[0].annotation(), "#prod #pci Payments production");
+
+        assert!(profiles[1].tags.is_empty());
+        assert_eq!(profiles[1].description, None);
+        assert!(profiles[1].hidden);
+        assert_eq!(profiles[1].annotation(), "");
+    }
+
+    #[test]
+    fn test_load_profiles_legacy_sso() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("profile client1-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client2-prod-readonly"))
+            .set("sso_start_url", "https://legacy.example.com/start")
+            .set("sso_region", "eu-west-1")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "ReadOnlyAccess");
+
+        ini.with_section(Some("profile client3-prod-nourl"))
+            .set("sso_region", "eu-west-1")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "ReadOnlyAccess");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0].sso_start_url, "https://example.com");
+        assert_eq!(profiles[1].name, "client2-prod-readonly");
+        assert_eq!(profiles[1].sso_start_url, "https://legacy.example.com/start");
+        assert_eq!(profiles[1].sso_session, None);
+        assert_eq!(profiles[1].sso_region.as_deref(), Some("eu-west-1"));
+    }
+
+    #[test]
+    fn test_load_profiles_chained_roles() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("profile client1-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client1-prod-deploy"))
+            .set("role_arn", "arn:aws:iam::987654321098:role/ci/Deploy")
+            .set("source_profile", "client1-dev-admin");
+
+        ini.with_section(Some("profile client1-audit-readonly"))
+            .set("role_arn", "arn:aws:iam::111111111111:role/Audit")
+            .set("source_profile", "client1-prod-deploy");
+
+        ini.with_section(Some("profile client2-ec2-ops"))
+            .set("role_arn", "arn:aws:iam::222222222222:role/Ops")
+            .set("credential_source", "Ec2InstanceMetadata");
+
+        ini.with_section(Some("profile client2-ec2-audit"))
+            .set("role_arn", "arn:aws:iam::333333333333:role/Audit")
+            .set("source_profile", "client2-ec2-ops");
+
+        ini.with_section(Some("profile client2-loop-a"))
+            .set("role_arn", "arn:aws:iam::222222222222:role/A")
+            .set("source_profile", "client2-loop-b");
+
+        ini.with_section(Some("profile client2-loop-b"))
+            .set("role_arn", "arn:aws:iam::222222222222:role/B")
+            .set("source_profile", "client2-loop-a");
+
+        ini.with_section(Some("profile client2-missing-source"))
+            .set("role_arn", "arn:aws:iam::222222222222:role/C")
+            .set("source_profile", "does-not-exist");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        assert_eq!(profiles.len(), 5);
+
+        assert_eq!(profiles[0].sso_profile(), Some("client1-dev-admin"));
+
+        assert_eq!(profiles[1].name, "client1-prod-deploy");
+        assert_eq!(profiles[1].chain, vec!["client1-dev-admin"]);
+        assert_eq!(profiles[1].sso_profile(), Some("client1-dev-admin"));
+        assert_eq!(profiles[1].sso_account_id, "123456789012");
+        assert_eq!(profiles[1].sso_start_url, "https://example.com");
+
+        assert_eq!(profiles[2].chain, vec!["client1-prod-deploy", "client1-dev-admin"]);
+        assert_eq!(profiles[2].sso_profile(), Some("client1-dev-admin"));
+
+        assert_eq!(profiles[3].name, "client2-ec2-ops");
+        assert!(profiles[3].chain.is_empty());
+        assert_eq!(profiles[3].sso_profile(), None);
+        assert_eq!(profiles[3].credential_source.as_deref(), Some("Ec2InstanceMetadata"));
+
+        // Two hops down to a credential_source, no SSO login anywhere
+        assert_eq!(profiles[4].name, "client2-ec2-audit");
+        assert_eq!(profiles[4].chain, vec!["client2-ec2-ops"]);
+        assert_eq!(profiles[4].credential_source.as_deref(), Some("Ec2InstanceMetadata"));
+        assert_eq!(profiles[4].sso_profile(), None);
+    }
+
+    #[test]
+    fn test_parse_role_arn() {
+        assert_eq!(
+            parse_role_arn("arn:aws:iam::123456789012:role/Admin"),
+            Some(("123456789012".to_string(), "Admin".to_string()))
+        );
+        assert_eq!(
+            parse_role_arn("arn:aws:iam::123456789012:role/path/to/Admin"),
+            Some(("123456789012".to_string(), "Admin".to_string()))
+        );
+        assert_eq!(parse_role_arn("arn:aws:iam::123456789012:user/bob"), None);
+        assert_eq!(parse_role_arn("not-an-arn"), None);
+    }
+
+    #[test]
+    fn test_load_profiles_diagnostics() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("sso-session broken"))
+            .set("sso_region", "us-east-1");
+
+        ini.with_section(Some("profile client1-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client1-dev-copy"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client1-prod-admin"))
+            .set("sso_session", "example")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client1-test-admin"))
+            .set("sso_session", "missing")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile oddlynamed"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "ReadOnlyAccess");
+
+        ini.with_section(Some("profile static"))
+            .set("region", "us-east-1");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let report =
+            load_profiles_with_diagnostics(&[temp_file.path().to_path_buf()], &Naming::default())
+                .unwrap();
+        assert_eq!(report.profiles.len(), 3);
+        assert!(report.has_errors());
+
+        let messages: Vec<(&str, Severity, &str)> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.section.as_str(), d.severity, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("sso-session broken", Severity::Error, "sso-session broken has no sso_start_url"),
+                ("profile client1-prod-admin", Severity::Error, "missing sso_account_id"),
+                ("profile client1-test-admin", Severity::Error, "sso_session missing does not exist"),
+                (
+                    "profile oddlynamed",
+                    Severity::Warning,
+                    "name doesn't match the naming scheme, grouped under 'other'"
+                ),
+                ("profile static", Severity::Warning, "not an SSO profile, skipped"),
+                (
+                    "profile client1-dev-copy",
+                    Severity::Error,
+                    "account 123456789012 and role AdministratorAccess are already used by profile client1-dev-admin"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_profiles_with_diagnostics_parse_error() {
+        let temp_file = NamedTempFile::new().unwrap();
+        std::fs::write(temp_file.path(), "[profile broken\nsso_session = x\n").unwrap();
+
+        let paths = [temp_file.path().to_path_buf()];
+        let error = load_profiles_with_diagnostics(&paths, &Naming::default()).err().unwrap();
+        assert!(error.starts_with("Failed to parse "), "{}", error);
+
+        let missing = [PathBuf::from("/does/not/exist/config")];
+        assert_eq!(
+            load_profiles_with_diagnostics(&missing, &Naming::default()).err().unwrap(),
+            "AWS config /does/not/exist/config not found"
+        );
+    }
+
+    #[test]
+    fn test_load_profiles_multiple_files() {
+        let personal = NamedTempFile::new().unwrap();
+        let shared = NamedTempFile::new().unwrap();
+
+        let mut ini = Ini::new();
+        ini.with_section(Some("profile me-dev-admin"))
+            .set("sso_session", "team")
+            .set("sso_account_id", "111111111111")
+            .set("sso_role_name", "AdministratorAccess");
+        ini.with_section(Some("profile team-prod-readonly"))
+            .set("sso_session", "team")
+            .set("sso_account_id", "222222222222")
+            .set("sso_role_name", "PersonalReadOnly");
+        ini.write_to_file(personal.path()).unwrap();
+
+        let mut ini = Ini::new();
+        ini.with_section(Some("sso-session team"))
+            .set("sso_start_url", "https://team.example.com");
+        ini.with_section(Some("profile team-prod-readonly"))
+            .set("sso_session", "team")
+            .set("sso_account_id", "222222222222")
+            .set("sso_role_name", "ReadOnlyAccess");
+        ini.with_section(Some("profile team-dev-admin"))
+            .set("sso_session", "team")
+            .set("sso_account_id", "333333333333")
+            .set("sso_role_name", "AdministratorAccess");
+        ini.write_to_file(shared.path()).unwrap();
+
+        let missing = PathBuf::from("/does/not/exist.ini");
+        let paths = [
+            personal.path().to_path_buf(),
+            shared.path().to_path_buf(),
+            missing.clone(),
+        ];
+        let report = load_profiles_with_diagnostics(&paths, &Naming::default()).unwrap();
+
+        let names: Vec<(&str, &Path)> = report
+            .profiles
+            .iter()
+            .map(|p| (p.name.as_str(), p.source_file.as_path()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("me-dev-admin", personal.path()),
+                ("team-prod-readonly", personal.path()),
+                ("team-dev-admin", shared.path()),
+            ]
+        );
+        // The sso-session from the shared file is resolved for personal profiles
+        assert_eq!(report.profiles[0].sso_start_url, "https://team.example.com");
+        assert_eq!(
+            report.profiles[0].referenced_sections,
+            vec![("sso-session team".to_string(), shared.path().to_path_buf())]
+        );
+
+        assert_eq!(report.diagnostics.len(), 2);
+        assert_eq!(report.diagnostics[0].file, missing);
+        assert_eq!(report.diagnostics[0].section, "");
+        assert_eq!(report.diagnostics[1].file, shared.path());
+        assert_eq!(report.diagnostics[1].section, "profile team-prod-readonly");
+        assert_eq!(
+            report.diagnostics[1].message,
+            format!("already defined in {}, skipped", personal.path().display())
+        );
+    }
+
+    fn write_naming_fixture(temp_file: &NamedTempFile) {
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("profile acme_prod__Admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile client1-dev-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+    }
+
+    #[test]
+    fn test_load_profiles_pattern_scheme() {
+        let temp_file = NamedTempFile::new().unwrap();
+        write_naming_fixture(&temp_file);
+
+        let naming = Naming::from_settings(Some(&NamingSettings {
+            pattern: Some(r"^(?P<client>[^_]+)_(?P<account>.+)__(?P<role>.+)$".to_string()),
+            ..Default::default()
+        }))
+        .unwrap();
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &naming);
+        assert_eq!(profiles.len(), 2);
+
+        assert_eq!(profiles[0].path, vec!["acme", "prod", "Admin"]);
+
+        assert_eq!(profiles[1].path, vec!["other", "client1-dev-admin", "AdministratorAccess"]);
+    }
+
+    #[test]
+    fn test_fallback_path_depth() {
+        let naming_with = |levels: &[&str]| {
+            Naming::from_settings(Some(&NamingSettings {
+                levels: Some(levels.iter().map(|level| level.to_string()).collect()),
+                separator: Some("/".to_string()),
+                ..Default::default()
+            }))
+            .unwrap()
+        };
+        assert_eq!(naming_with(&["account"]).path_for("legacy", "Admin"), vec!["legacy"]);
+        assert_eq!(naming_with(&["client", "account"]).path_for("legacy", "Admin"), vec!["other", "legacy"]);
+        assert_eq!(
+            naming_with(&["org", "env", "account", "role"]).path_for("legacy", "Admin"),
+            vec!["other", "other", "legacy", "Admin"]
+        );
+    }
+
+    #[test]
+    fn test_load_profiles_separator_scheme() {
+        let temp_file = NamedTempFile::new().unwrap();
+        write_naming_fixture(&temp_file);
+
+        let naming = Naming::from_settings(Some(&NamingSettings {
+            separator: Some("_".to_string()),
+            fallback: Some("misc".to_string()),
+            ..Default::default()
+        }))
+        .unwrap();
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &naming);
+        assert_eq!(profiles.len(), 2);
+
+        // Empty segments from the double separator don't match
+        assert_eq!(profiles[0].path, vec!["misc", "acme_prod__Admin", "AdministratorAccess"]);
+
+        assert_eq!(profiles[1].path, vec!["misc", "client1-dev-admin", "AdministratorAccess"]);
+    }
+
+    #[test]
+    fn test_load_profiles_default_scheme_fallback() {
+        let temp_file = NamedTempFile::new().unwrap();
+        write_naming_fixture(&temp_file);
+
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &Naming::default());
+        assert_eq!(profiles.len(), 2);
+
+        // As deep as the naming scheme, with the SSO role as the role
+        assert_eq!(profiles[0].path, vec!["other", "acme_prod__Admin", "AdministratorAccess"]);
+
+        assert_eq!(profiles[1].path, vec!["client1", "dev", "admin"]);
+    }
+
+    #[test]
+    fn test_load_profiles_five_levels() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut ini = Ini::new();
+
+        ini.with_section(Some("sso-session example"))
+            .set("sso_start_url", "https://example.com");
+
+        ini.with_section(Some("profile acme-platform-prod-shared-admin"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "123456789012")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.with_section(Some("profile acme-platform-dev"))
+            .set("sso_session", "example")
+            .set("sso_account_id", "987654321098")
+            .set("sso_role_name", "AdministratorAccess");
+
+        ini.write_to_file(temp_file.path()).unwrap();
+
+        let levels: Vec<String> = ["org", "bu", "env", "account", "role"]
+            .iter()
+            .map(|level| level.to_string())
+            .collect();
+        let naming = Naming::from_settings(Some(&NamingSettings {
+            levels: Some(levels.clone()),
+            ..Default::default()
+        }))
+        .unwrap();
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &naming);
+        assert_eq!(profiles[0].path, vec!["acme", "platform", "prod", "shared", "admin"]);
+        assert_eq!(
+            profiles[1].path,
+            vec!["other", "other", "other", "acme-platform-dev", "AdministratorAccess"]
+        );
+
+        let naming = Naming::from_settings(Some(&NamingSettings {
+            levels: Some(levels),
+            pattern: Some(
+                r"^(?P<org>\w+)-(?P<bu>\w+)-(?P<env>\w+)-(?P<account>\w+)-(?P<role>\w+)$"
+                    .to_string(),
+            ),
+            ..Default::default()
+        }))
+        .unwrap();
+        let profiles = load_profiles(&[temp_file.path().to_path_buf()], &naming);
+        assert_eq!(profiles[0].path, vec!["acme", "platform", "prod", "shared", "admin"]);
+    }
+
+    fn path_profile(name: &str, path: &[&str]) -> Profile {
+        Profile {
+            name: name.to_string(),
+            path: path.iter().map(|segment| segment.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select_by_levels() {
+        let profiles = vec![
+            path_profile("a-dev-admin", &["a", "dev", "admin"]),
+            path_profile("a-dev-readonly", &["a", "dev", "readonly"]),
+            path_profile("a-prod-admin", &["a", "prod", "admin"]),
+            path_profile("b-dev-admin", &["b", "dev", "admin"]),
+            path_profile("odd", &["other", "odd"]),
+        ];
+        let levels = Naming::default().levels;
+
+        let mut prompts = Vec::new();
+        let aliases = Aliases::default();
+        let profile = select_by_levels(&profiles, &levels, &aliases, &[Some("a".to_string())], |prompt, options| {
+            prompts.push((prompt.to_string(), options.clone()));
+            options.last().cloned()
+        })
+        .unwrap();
+        assert_eq!(profile.name, "a-prod-admin");
+        assert_eq!(
+            prompts,
+            vec![
+                ("Select Account".to_string(), vec!["dev".to_string(), "prod".to_string()]),
+                ("Select Role".to_string(), vec!["admin".to_string()]),
+            ]
+        );
+
+        let profile = select_by_levels(&profiles, &levels, &aliases, &[], |_, options| {
+            options.last().cloned()
+        })
+        .unwrap();
+        assert_eq!(profile.name, "odd");
+
+        let preselected = [Some("b".to_string()), Some("prod".to_string())];
+        let result = select_by_levels(&profiles, &levels, &aliases, &preselected, |_, _| None);
+        assert_eq!(result.unwrap_err(), "No matching profile found");
+
+        let result = select_by_levels(&profiles, &levels, &aliases, &[], |_, _| None);
+        assert_eq!(result.unwrap_err(), "Selection incomplete");
+    }
+
+    #[test]
+    fn test_select_by_levels_aliases() {
+        let profiles = vec![
+            path_profile("acme-shared-AWSAdministratorAccess", &["acme", "shared", "AWSAdministratorAccess"]),
+            path_profile("acme-shared-AdministratorAccess", &["acme", "shared", "AdministratorAccess"]),
+            path_profile("acme-shared-AWSReadOnlyAccess", &["acme", "shared", "AWSReadOnlyAccess"]),
+        ];
+        let naming = Naming::default();
+        let table = HashMap::from([(
+            "role".to_string(),
+            HashMap::from([
+                ("AWSAdministratorAccess".to_string(), "admin".to_string()),
+                ("AWSReadOnlyAccess".to_string(), "readonly".to_string()),
+            ]),
+        )]);
+        let aliases = Aliases::from_settings(&naming, Some(&table)).unwrap();
+
+        let mut offered = Vec::new();
+        let profile = select_by_levels(&profiles, &naming.levels, &aliases, &[], |_, options| {
+            offered.push(options.clone());
+            options
+                .iter()
+                .find(|option| option.as_str() == "admin")
+                .or(options.first())
+                .cloned()
+        })
+        .unwrap();
+        assert_eq!(profile.name, "acme-shared-AWSAdministratorAccess");
+        assert_eq!(offered[2], vec!["AdministratorAccess", "admin", "readonly"]);
+
+        // Both the alias and the raw segment can be preselected
+        for role in ["readonly", "AWSReadOnlyAccess"] {
+            let preselected = [None, None, Some(role.to_string())];
+            let profile = select_by_levels(&profiles, &naming.levels, &aliases, &preselected, |_, options| {
+                options.first().cloned()
+            })
+            .unwrap();
+            assert_eq!(profile.name, "acme-shared-AWSReadOnlyAccess");
+        }
+
+        assert_eq!(
+            aliases.display_path(&profiles[0].path),
+            vec!["acme", "shared", "admin"]
+        );
+
+        // An alias that's also another role's raw name could mean either
+        let mut clashing = profiles.clone();
+        clashing.push(path_profile("acme-shared-admin", &["acme", "shared", "admin"]));
+        let preselected = [None, None, Some("admin".to_string())];
+        let result = select_by_levels(&clashing, &naming.levels, &aliases, &preselected, |_, options| {
+            options.first().cloned()
+        });
+        assert_eq!(result.unwrap_err(), "role admin is ambiguous, it matches AWSAdministratorAccess, admin");
+        // The raw name is still unique
+        let preselected = [None, None, Some("AWSAdministratorAccess".to_string())];
+        let profile = select_by_levels(&clashing, &naming.levels, &aliases, &preselected, |_, options| {
+            options.first().cloned()
+        })
+        .unwrap();
+        assert_eq!(profile.name, "acme-shared-AWSAdministratorAccess");
+
+        // Two profiles with one path can't be told apart
+        let mut duplicates = profiles.clone();
+        duplicates.push(path_profile("acme-shared-readonly-copy", &["acme", "shared", "AWSReadOnlyAccess"]));
+        let preselected = [None, None, Some("readonly".to_string())];
+        let result = select_by_levels(&duplicates, &naming.levels, &aliases, &preselected, |_, options| {
+            options.first().cloned()
+        });
+        assert_eq!(
+            result.unwrap_err(),
+            "Profiles acme-shared-AWSReadOnlyAccess, acme-shared-readonly-copy have the same path, rename one of them"
+        );
+
+        let unknown = HashMap::from([("team".to_string(), HashMap::new())]);
+        assert!(Aliases::from_settings(&naming, Some(&unknown)).is_err());
+    }
+
+    #[test]
+    fn test_naming_from_settings_errors() {
+        let both = NamingSettings {
+            pattern: Some("(?P<client>.+)".to_string()),
+            separator: Some("-".to_string()),
+            ..Default::default()
+        };
+        assert!(Naming::from_settings(Some(&both)).is_err());
+
+        let missing_group = NamingSettings {
+            pattern: Some("(?P<client>.+)-(?P<account>.+)".to_string()),
+            ..Default::default()
+        };
+        assert!(Naming::from_settings(Some(&missing_group)).is_err());
+
+        let invalid = NamingSettings {
+            pattern: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(Naming::from_settings(Some(&invalid)).is_err());
     }
 }
@@ -0,0 +1,127 @@
+use clap::ValueEnum;
+
+/// Syntax to print environment variables in.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ShellFormat {
+    /// `export NAME='value'`, for bash and zsh
+    #[value(alias = "zsh")]
+    Bash,
+    /// `set -gx NAME 'value'`
+    Fish,
+    /// `$Env:NAME = 'value'`
+    #[value(alias = "pwsh")]
+    Powershell,
+    /// `NAME=value`, for .env files
+    Dotenv,
+}
+
+impl ShellFormat {
+    /// Formats one variable assignment, quoting the value for the shell.
+    pub fn assignment(self, name: &str, value: &str) -> String {
+        match self {
+            ShellFormat::Bash => format!("export {}='{}'", name, value.replace('\'', r"'\''")),
+            ShellFormat::Fish => format!(
+                "set -gx {} '{}'",
+                name,
+                value.replace('\\', r"\\").replace('\'', r"\'")
+            ),
+            ShellFormat::Powershell => format!("$Env:{} = '{}'", name, value.replace('\'', "''")),
+            ShellFormat::Dotenv => format!("{}={}", name, value),
+        }
+    }
+
+    pub fn assignments(self, variables: &[(&str, String)]) -> String {
+        variables
+            .iter()
+            .map(|(name, value)| self.assignment(name, value) + "\n")
+            .collect()
+    }
+}
+
+/// Shells `shell-init` can write a wrapper function for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// A shell function that runs `auth` with its arguments and exports the
+/// selected profile, read from the `--print-profile` file, into the calling shell.
+pub fn wrapper_function(shell: Shell, name: &str) -> String {
+    let body = match shell {
+        Shell::Bash | Shell::Zsh => POSIX_WRAPPER,
+        Shell::Fish => FISH_WRAPPER,
+    };
+    body.replace("{name}", name)
+}
+
+const POSIX_WRAPPER: &str = r#"{name}() {
+    local profile_file profile region exit_code
+    profile_file="$(mktemp)" || return
+    command aws-sso-navigator auth --print-profile "$profile_file" "$@"
+    exit_code=$?
+    if [ "$exit_code" -eq 0 ] && [ -s "$profile_file" ]; then
+        { IFS= read -r profile; IFS= read -r region; } < "$profile_file"
+        export AWS_PROFILE="$profile"
+        if [ -n "$region" ]; then
+            export AWS_REGION="$region"
+        fi
+    fi
+    rm -f "$profile_file"
+    return "$exit_code"
+}
+"#;
+
+const FISH_WRAPPER: &str = r#"function {name}
+    set -l profile_file (mktemp); or return
+    command aws-sso-navigator auth --print-profile $profile_file $argv
+    set -l exit_code $status
+    if test $exit_code -eq 0; and test -s $profile_file
+        set -l lines (cat $profile_file)
+        set -gx AWS_PROFILE $lines[1]
+        if test -n "$lines[2]"
+            set -gx AWS_REGION $lines[2]
+        end
+    end
+    rm -f $profile_file
+    return $exit_code
+end
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_quoting() {
+        assert_eq!(ShellFormat::Bash.assignment("A", "it's"), r"export A='it'\''s'");
+        assert_eq!(ShellFormat::Fish.assignment("A", "it's"), r"set -gx A 'it\'s'");
+        assert_eq!(ShellFormat::Powershell.assignment("A", "it's"), "$Env:A = 'it''s'");
+        assert_eq!(ShellFormat::Dotenv.assignment("A", "a/b+c="), "A=a/b+c=");
+    }
+
+    #[test]
+    fn test_assignments() {
+        let variables = [
+            ("AWS_ACCESS_KEY_ID", "AKIA".to_string()),
+            ("AWS_REGION", "eu-west-1".to_string()),
+        ];
+        assert_eq!(
+            ShellFormat::Fish.assignments(&variables),
+            "set -gx AWS_ACCESS_KEY_ID 'AKIA'\nset -gx AWS_REGION 'eu-west-1'\n"
+        );
+    }
+
+    #[test]
+    fn test_wrapper_function() {
+        let bash = wrapper_function(Shell::Bash, "awsnav");
+        assert!(bash.starts_with("awsnav() {\n"));
+        assert!(bash.contains("command aws-sso-navigator auth --print-profile \"$profile_file\" \"$@\""));
+        assert_eq!(wrapper_function(Shell::Zsh, "awsnav"), bash);
+
+        let fish = wrapper_function(Shell::Fish, "awsp");
+        assert!(fish.starts_with("function awsp\n"));
+        assert!(fish.contains("set -gx AWS_PROFILE $lines[1]"));
+    }
+}
@@ -0,0 +1,314 @@
+use crate::atomic_file::write_secret;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An SSO access token as cached in `~/.aws/sso/cache`, in the AWS CLI's format.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedToken {
+    pub start_url: String,
+    pub region: String,
+    pub access_token: String,
+    pub expires_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_expires_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+}
+
+impl CachedToken {
+    /// Parses `expiresAt`, accepting RFC 3339 and the `2024-01-01T00:00:00UTC`
+    /// form older AWS CLI versions write.
+    pub fn expiry(&self) -> Option<DateTime<Utc>> {
+        parse_timestamp(&self.expires_at)
+    }
+
+    /// Whether the token is still valid at `now`, counting tokens that expire
+    /// within `skew` as already expired.
+    pub fn is_valid_at(&self, now: DateTime<Utc>, skew: Duration) -> bool {
+        self.expiry().is_some_and(|expiry| expiry - skew > now)
+    }
+
+    /// Whether the token can be refreshed without the browser: it has a refresh
+    /// token and a client registration that hasn't expired.
+    pub fn can_refresh(&self, now: DateTime<Utc>) -> bool {
+        self.refresh_token.is_some()
+            && self.client_id.is_some()
+            && self.client_secret.is_some()
+            && self
+                .registration_expires_at
+                .as_deref()
+                .and_then(parse_timestamp)
+                .is_some_and(|expiry| expiry > now)
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%SUTC").map(|time| time.and_utc())
+        })
+        .ok()
+}
+
+pub fn cache_dir() -> PathBuf {
+    home_dir().unwrap().join(".aws").join("sso").join("cache")
+}
+
+/// File name the AWS CLI uses for a token: the SHA-1 of the sso-session name,
+/// or of the start URL for legacy profiles.
+pub fn cache_key(sso_session: Option<&str>, start_url: &str) -> String {
+    let digest = Sha1::digest(sso_session.unwrap_or(start_url).as_bytes());
+    format!("{:x}", digest)
+}
+
+pub fn token_path(cache_dir: &Path, sso_session: Option<&str>, start_url: &str) -> PathBuf {
+    cache_dir.join(format!("{}.json", cache_key(sso_session, start_url)))
+}
+
+pub fn read_token(path: &Path) -> Option<CachedToken> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Finds the cached token for an sso-session or legacy start URL. Looks up the
+/// file named by the cache key first, then any token issued for the same start
+/// URL, preferring the one that expires last.
+pub fn find_token(cache_dir: &Path, sso_session: Option<&str>, start_url: &str) -> Option<CachedToken> {
+    if let Some(token) = read_token(&token_path(cache_dir, sso_session, start_url)) {
+        return Some(token);
+    }
+
+    tokens_for_start_url(cache_dir, start_url)
+        .into_iter()
+        .map(|(_, token)| token)
+        .max_by_key(|token| token.expiry())
+}
+
+/// Every cached token issued for `start_url`, with its file.
+fn tokens_for_start_url(cache_dir: &Path, start_url: &str) -> Vec<(PathBuf, CachedToken)> {
+    let wanted = normalize_start_url(start_url);
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| read_token(&path).map(|token| (path, token)))
+        .filter(|(_, token)| normalize_start_url(&token.start_url) == wanted)
+        .collect()
+}
+
+/// Token files for an sso-session or legacy start URL: the one named by the
+/// cache key plus any other token issued for the same start URL.
+pub fn token_files(cache_dir: &Path, sso_session: Option<&str>, start_url: &str) -> Vec<PathBuf> {
+    let mut files = vec![token_path(cache_dir, sso_session, start_url)];
+    for (path, _) in tokens_for_start_url(cache_dir, start_url) {
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    files.retain(|path| path.exists());
+    files
+}
+
+/// Where the AWS CLI caches role credentials.
+pub fn cli_cache_dir() -> PathBuf {
+    home_dir().unwrap().join(".aws").join("cli").join("cache")
+}
+
+/// File the AWS CLI caches an SSO profile's role credentials in: the SHA-1 of
+/// its arguments as compact JSON with sorted keys.
+pub fn cli_role_cache_path(
+    cli_cache_dir: &Path,
+    sso_session: Option<&str>,
+    start_url: &str,
+    account_id: &str,
+    role_name: &str,
+) -> PathBuf {
+    let mut args = serde_json::json!({ "accountId": account_id, "roleName": role_name });
+    match sso_session {
+        Some(session) => args["sessionName"] = session.into(),
+        None => args["startUrl"] = start_url.into(),
+    }
+    let digest = Sha1::digest(args.to_string().as_bytes());
+    cli_cache_dir.join(format!("{:x}.json", digest))
+}
+
+fn normalize_start_url(url: &str) -> &str {
+    url.trim_end_matches('/').trim_end_matches('#').trim_end_matches('/')
+}
+
+pub fn write_token(path: &Path, token: &CachedToken) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create cache directory: {}", e))?;
+    }
+    let contents = serde_json::to_string(token)
+        .map_err(|e| format!("Failed to serialize token: {}", e))?;
+    // Renamed into place, so the AWS CLI never reads a half-written token, and
+    // private, since it holds the access and refresh tokens
+    write_secret(path, contents.as_bytes()).map_err(|e| format!("Failed to write token cache: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_matches_aws_cli() {
+        // sha1("my-sso") and sha1("https://example.awsapps.com/start")
+        assert_eq!(
+            cache_key(Some("my-sso"), "https://example.awsapps.com/start"),
+            "0ad374308c5a4e22f723adf10145eafad7c4031c"
+        );
+        assert_eq!(
+            cache_key(None, "https://example.awsapps.com/start"),
+            "e8be5486177c5b5392bd9aa76563515b29358e6e"
+        );
+    }
+
+    fn token(start_url: &str, expires_at: &str) -> CachedToken {
+        CachedToken {
+            start_url: start_url.to_string(),
+            region: "us-east-1".to_string(),
+            access_token: "token".to_string(),
+            expires_at: expires_at.to_string(),
+            client_id: None,
+            client_secret: None,
+            registration_expires_at: None,
+            refresh_token: None,
+        }
+    }
+
+    #[test]
+    fn test_token_validity_with_skew() {
+        let now = DateTime::parse_from_rfc3339("2030-01-01T00:00:00Z").unwrap().to_utc();
+        let current = token("https://example.com", "2030-01-01T00:10:00Z");
+
+        assert!(current.is_valid_at(now, Duration::zero()));
+        assert!(current.is_valid_at(now, Duration::minutes(5)));
+        assert!(!current.is_valid_at(now, Duration::minutes(10)));
+        assert!(!current.is_valid_at(now + Duration::hours(1), Duration::zero()));
+
+        let legacy = token("https://example.com", "2030-01-01T00:10:00UTC");
+        assert_eq!(legacy.expiry(), current.expiry());
+        assert!(!token("https://example.com", "soon").is_valid_at(now, Duration::zero()));
+    }
+
+    #[test]
+    fn test_can_refresh() {
+        let now = DateTime::parse_from_rfc3339("2030-01-01T00:00:00Z").unwrap().to_utc();
+        let mut expired = token("https://example.com", "2029-12-31T00:00:00Z");
+        assert!(!expired.can_refresh(now));
+
+        expired.client_id = Some("cid".to_string());
+        expired.client_secret = Some("secret".to_string());
+        expired.refresh_token = Some("refresh".to_string());
+        expired.registration_expires_at = Some("2030-02-01T00:00:00Z".to_string());
+        assert!(expired.can_refresh(now));
+        assert!(!expired.can_refresh(now + Duration::days(31)));
+
+        expired.refresh_token = None;
+        assert!(!expired.can_refresh(now));
+    }
+
+    #[test]
+    fn test_find_token() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let start_url = "https://example.awsapps.com/start";
+
+        assert_eq!(find_token(dir, Some("my-sso"), start_url), None);
+
+        // Tokens written under another key are matched by start URL
+        write_token(&dir.join("older.json"), &token("https://example.awsapps.com/start/#", "2030-01-01T00:00:00Z")).unwrap();
+        write_token(&dir.join("newer.json"), &token(start_url, "2030-06-01T00:00:00Z")).unwrap();
+        write_token(&dir.join("other.json"), &token("https://other.awsapps.com/start", "2031-01-01T00:00:00Z")).unwrap();
+        fs::write(dir.join("botocore-client-id-us-east-1.json"), r#"{"clientId":"cid"}"#).unwrap();
+        assert_eq!(
+            find_token(dir, Some("my-sso"), start_url).map(|t| t.expires_at),
+            Some("2030-06-01T00:00:00Z".to_string())
+        );
+
+        // The session-name hash wins over a start URL match
+        write_token(&token_path(dir, Some("my-sso"), start_url), &token(start_url, "2029-01-01T00:00:00Z")).unwrap();
+        assert_eq!(
+            find_token(dir, Some("my-sso"), start_url).map(|t| t.expires_at),
+            Some("2029-01-01T00:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn test_token_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let start_url = "https://example.awsapps.com/start";
+
+        let keyed = token_path(dir, Some("my-sso"), start_url);
+        write_token(&keyed, &token(start_url, "2030-01-01T00:00:00Z")).unwrap();
+        write_token(&dir.join("legacy.json"), &token("https://example.awsapps.com/start/", "2030-01-01T00:00:00Z")).unwrap();
+        write_token(&dir.join("other.json"), &token("https://other.awsapps.com/start", "2030-01-01T00:00:00Z")).unwrap();
+
+        let mut files = token_files(dir, Some("my-sso"), start_url);
+        files.sort();
+        let mut expected = vec![keyed, dir.join("legacy.json")];
+        expected.sort();
+        assert_eq!(files, expected);
+        assert!(token_files(dir, Some("unknown"), "https://unknown.example.com").is_empty());
+    }
+
+    #[test]
+    fn test_cli_role_cache_path_matches_aws_cli() {
+        let dir = Path::new("/cache");
+        // sha1('{"accountId":"123456789012","roleName":"Admin","sessionName":"my-sso"}')
+        assert_eq!(
+            cli_role_cache_path(dir, Some("my-sso"), "https://example.com", "123456789012", "Admin"),
+            dir.join("c30b99aca80d8f5b3178e353f05b2abcc9a4ccd3.json")
+        );
+        // sha1('{"accountId":"123456789012","roleName":"Admin","startUrl":"https://example.com"}')
+        assert_eq!(
+            cli_role_cache_path(dir, None, "https://example.com", "123456789012", "Admin"),
+            dir.join("20fdebf46ad22d8896149a26398f9d0f8b543032.json")
+        );
+    }
+
+    #[test]
+    fn test_token_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = token_path(temp_dir.path(), Some("my-sso"), "https://example.com");
+        let token = CachedToken {
+            start_url: "https://example.com".to_string(),
+            region: "us-east-1".to_string(),
+            access_token: "token".to_string(),
+            expires_at: "2030-01-01T00:00:00Z".to_string(),
+            client_id: Some("client".to_string()),
+            client_secret: None,
+            registration_expires_at: None,
+            refresh_token: None,
+        };
+
+        write_token(&path, &token).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\"startUrl\":\"https://example.com\""));
+        assert!(contents.contains("\"expiresAt\":\"2030-01-01T00:00:00Z\""));
+        assert!(!contents.contains("clientSecret"));
+        assert_eq!(read_token(&path), Some(token));
+        // The temporary file was renamed into place
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+    }
+}
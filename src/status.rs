@@ -0,0 +1,188 @@
+use crate::profile::SsoLogin;
+use crate::sso_cache::find_token;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenState {
+    Valid,
+    Expired,
+    Missing,
+}
+
+impl TokenState {
+    fn label(self) -> &'static str {
+        match self {
+            TokenState::Valid => "valid",
+            TokenState::Expired => "expired",
+            TokenState::Missing => "missing",
+        }
+    }
+}
+
+/// The cached token state of one SSO login.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SessionStatus {
+    pub name: String,
+    pub sso_session: Option<String>,
+    pub start_url: String,
+    pub token: TokenState,
+    pub expires_at: Option<String>,
+    /// Seconds until the token expires, negative once it has.
+    pub remaining_seconds: Option<i64>,
+    pub profiles: usize,
+}
+
+pub fn session_statuses(logins: &[SsoLogin], cache_dir: &Path, now: DateTime<Utc>) -> Vec<SessionStatus> {
+    logins
+        .iter()
+        .map(|login| {
+            let expiry = find_token(cache_dir, login.sso_session.as_deref(), &login.start_url)
+                .map(|token| token.expiry());
+            let token = match expiry {
+                None => TokenState::Missing,
+                Some(Some(expiry)) if expiry > now => TokenState::Valid,
+                Some(_) => TokenState::Expired,
+            };
+            let expiry = expiry.flatten();
+            SessionStatus {
+                name: login.name.clone(),
+                sso_session: login.sso_session.clone(),
+                start_url: login.start_url.clone(),
+                token,
+                expires_at: expiry.map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+                remaining_seconds: expiry.map(|time| (time - now).num_seconds()),
+                profiles: login.profiles.len(),
+            }
+        })
+        .collect()
+}
+
+/// Formats a duration like `7h 12m`, or `expired 1h 30m ago` when negative.
+fn format_remaining(seconds: i64) -> String {
+    let amount = seconds.abs();
+    let text = if amount >= 86400 {
+        format!("{}d {}h", amount / 86400, amount % 86400 / 3600)
+    } else if amount >= 3600 {
+        format!("{}h {}m", amount / 3600, amount % 3600 / 60)
+    } else {
+        format!("{}m", amount / 60)
+    };
+    if seconds < 0 {
+        format!("expired {} ago", text)
+    } else {
+        text
+    }
+}
+
+pub fn render_table(statuses: &[SessionStatus]) -> String {
+    let rows: Vec<[String; 5]> = statuses
+        .iter()
+        .map(|status| {
+            [
+                status.name.clone(),
+                status.token.label().to_string(),
+                status.expires_at.clone().unwrap_or_else(|| "-".to_string()),
+                status
+                    .remaining_seconds
+                    .map(format_remaining)
+                    .unwrap_or_else(|| "-".to_string()),
+                status.profiles.to_string(),
+            ]
+        })
+        .collect();
+
+    let header = ["SESSION", "TOKEN", "EXPIRES", "REMAINING", "PROFILES"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell))
+                .collect();
+            line.join("  ").trim_end().to_string() + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sso_cache::{token_path, write_token, CachedToken};
+
+    fn login(name: &str, sso_session: Option<&str>, profiles: usize) -> SsoLogin {
+        SsoLogin {
+            name: name.to_string(),
+            sso_session: sso_session.map(String::from),
+            start_url: format!("https://{}.awsapps.com/start", name),
+            region: None,
+            profiles: (0..profiles).map(|i| format!("profile-{}", i)).collect(),
+        }
+    }
+
+    fn write_fixture(cache_dir: &Path, login: &SsoLogin, expires_at: &str) {
+        let token = CachedToken {
+            start_url: login.start_url.clone(),
+            region: "us-east-1".to_string(),
+            access_token: "token".to_string(),
+            expires_at: expires_at.to_string(),
+            client_id: None,
+            client_secret: None,
+            registration_expires_at: None,
+            refresh_token: None,
+        };
+        let path = token_path(cache_dir, login.sso_session.as_deref(), &login.start_url);
+        write_token(&path, &token).unwrap();
+    }
+
+    #[test]
+    fn test_session_statuses() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let now = DateTime::parse_from_rfc3339("2030-01-01T00:00:00Z").unwrap().to_utc();
+        let valid = login("acme", Some("acme"), 3);
+        let expired = login("globex", Some("globex"), 1);
+        let missing = login("legacy", None, 2);
+        write_fixture(temp_dir.path(), &valid, "2030-01-01T07:12:00Z");
+        write_fixture(temp_dir.path(), &expired, "2029-12-29T00:00:00Z");
+
+        let statuses = session_statuses(&[valid, expired, missing], temp_dir.path(), now);
+        assert_eq!(
+            statuses.iter().map(|s| s.token).collect::<Vec<_>>(),
+            vec![TokenState::Valid, TokenState::Expired, TokenState::Missing]
+        );
+        assert_eq!(statuses[0].remaining_seconds, Some(7 * 3600 + 12 * 60));
+        assert_eq!(statuses[1].expires_at.as_deref(), Some("2029-12-29T00:00:00Z"));
+        assert_eq!(statuses[2].expires_at, None);
+
+        assert_eq!(
+            render_table(&statuses),
+            "SESSION  TOKEN    EXPIRES               REMAINING          PROFILES\n\
+             acme     valid    2030-01-01T07:12:00Z  7h 12m             3\n\
+             globex   expired  2029-12-29T00:00:00Z  expired 3d 0h ago  1\n\
+             legacy   missing  -                     -                  2\n"
+        );
+
+        let json = serde_json::to_value(&statuses).unwrap();
+        assert_eq!(json[0]["token"], "valid");
+        assert_eq!(json[0]["sso_session"], "acme");
+        assert_eq!(json[2]["remaining_seconds"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(59), "0m");
+        assert_eq!(format_remaining(3 * 60), "3m");
+        assert_eq!(format_remaining(26 * 3600 + 60), "1d 2h");
+        assert_eq!(format_remaining(-90 * 60), "expired 1h 30m ago");
+    }
+}
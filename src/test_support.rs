@@ -0,0 +1,175 @@
+use crate::aws::SessionCheck;
+use crate::backend::Backend;
+use crate::oidc::expires_in;
+use crate::portal::{Account, Role, RoleCredentials};
+use crate::profile::Profile;
+use crate::sso_cache::CachedToken;
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+/// A request received by the mock server.
+pub struct Recorded {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+/// Serves `responses` as (status, JSON body) in order, one per connection, and
+/// returns the base URL plus a handle that yields the recorded requests.
+pub fn mock_server(responses: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<Recorded>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        let mut recorded = Vec::new();
+        for (status, body) in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+
+            let mut stream = reader.into_inner();
+            write!(
+                stream,
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+
+            recorded.push(Recorded {
+                method,
+                path,
+                body: String::from_utf8(request_body).unwrap(),
+            });
+        }
+        recorded
+    });
+
+    (base_url, handle)
+}
+
+/// Access token the fake backend issues and expects back.
+pub const FAKE_ACCESS_TOKEN: &str = "fake-access-token";
+
+/// An in-memory backend that records its calls, e.g. `login client-dev-admin`.
+#[derive(Default)]
+pub struct FakeBackend {
+    /// Whether `check_session` reports a valid session.
+    pub valid_session: bool,
+    /// Whether `refresh` finds a refresh token.
+    pub refreshable: bool,
+    pub accounts: Vec<Account>,
+    pub roles: Vec<Role>,
+    /// Accounts whose roles can't be listed, as when access was just revoked.
+    pub inaccessible_accounts: Vec<String>,
+    pub calls: RefCell<Vec<String>>,
+}
+
+impl FakeBackend {
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn token(profile: &Profile) -> CachedToken {
+        CachedToken {
+            start_url: profile.sso_start_url.clone(),
+            region: profile.sso_region.clone().unwrap_or_default(),
+            access_token: FAKE_ACCESS_TOKEN.to_string(),
+            expires_at: expires_in(8 * 3600),
+            client_id: None,
+            client_secret: None,
+            registration_expires_at: None,
+            refresh_token: None,
+        }
+    }
+
+    fn authorize(access_token: &str) -> Result<(), String> {
+        if access_token == FAKE_ACCESS_TOKEN {
+            Ok(())
+        } else {
+            Err("SSO token was rejected, log in again".to_string())
+        }
+    }
+}
+
+impl Backend for FakeBackend {
+    fn login(&self, profile: &Profile) -> Result<CachedToken, String> {
+        self.record(format!("login {}", profile.name));
+        Ok(Self::token(profile))
+    }
+
+    fn check_session(&self, profile: &Profile, _check: &SessionCheck) -> bool {
+        self.record(format!("check_session {}", profile.name));
+        self.valid_session
+    }
+
+    fn refresh(&self, profile: &Profile) -> Result<Option<CachedToken>, String> {
+        self.record(format!("refresh {}", profile.name));
+        Ok(self.refreshable.then(|| Self::token(profile)))
+    }
+
+    fn list_accounts(&self, access_token: &str, _region: &str) -> Result<Vec<Account>, String> {
+        Self::authorize(access_token)?;
+        self.record("list_accounts".to_string());
+        Ok(self.accounts.clone())
+    }
+
+    fn list_roles(&self, access_token: &str, _region: &str, account_id: &str) -> Result<Vec<Role>, String> {
+        Self::authorize(access_token)?;
+        self.record(format!("list_roles {}", account_id));
+        if self.inaccessible_accounts.iter().any(|id| id == account_id) {
+            return Err(format!("Failed to list roles: no access to account {}", account_id));
+        }
+        Ok(self
+            .roles
+            .iter()
+            .filter(|role| role.account_id == account_id)
+            .cloned()
+            .collect())
+    }
+
+    fn role_credentials(
+        &self,
+        access_token: &str,
+        _region: &str,
+        account_id: &str,
+        role_name: &str,
+    ) -> Result<RoleCredentials, String> {
+        Self::authorize(access_token)?;
+        self.record(format!("role_credentials {} {}", account_id, role_name));
+        Ok(RoleCredentials {
+            access_key_id: "AKIAFAKE".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: "session".to_string(),
+            expiration: chrono::Utc::now() + chrono::Duration::hours(1),
+        })
+    }
+}
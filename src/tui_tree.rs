@@ -8,100 +8,91 @@ use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
     Frame, Terminal,
 };
-use std::{collections::HashMap, io};
-use crate::profile::Profile;
+use std::{
+    collections::{BTreeSet, HashSet},
+    io,
+};
+use crate::profile::{Aliases, Profile};
 
 struct TreeApp {
     profiles: Vec<Profile>,
+    /// Aliased path of each profile, in the same order as `profiles`.
+    display_paths: Vec<Vec<String>>,
     tree_items: Vec<TreeItem>,
     list_state: ListState,
-    selected_profile: Option<(String, String, String)>,
-    expanded: HashMap<String, bool>,
+    selected_profile: Option<String>,
+    expanded: HashSet<Vec<String>>,
 }
 
 #[derive(Clone)]
 struct TreeItem {
     label: String,
-    client: String,
-    account: String,
-    role: String,
-    level: usize,
+    path: Vec<String>,
+    /// Profile name for leaf items, `None` for folders.
+    profile: Option<String>,
 }
 
 impl TreeApp {
-    fn new(profiles: Vec<Profile>) -> Self {
-        let mut client_map: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
-        
-        for profile in &profiles {
-            client_map
-                .entry(profile.client.clone())
-                .or_default()
-                .entry(profile.account.clone())
-                .or_default()
-                .push(profile.role.clone());
-        }
-
+    fn new(profiles: Vec<Profile>, aliases: &Aliases) -> Self {
+        let display_paths = profiles
+            .iter()
+            .map(|p| aliases.display_path(&p.path))
+            .collect();
         let mut app = Self {
             profiles,
+            display_paths,
             tree_items: Vec::new(),
             list_state: ListState::default(),
             selected_profile: None,
-            expanded: HashMap::new(),
+            expanded: HashSet::new(),
         };
-        
-        app.rebuild_tree(&client_map);
+
+        app.rebuild_tree();
         app.list_state.select(Some(0));
         app
     }
-    
-    fn rebuild_tree(&mut self, client_map: &HashMap<String, HashMap<String, Vec<String>>>) {
-        self.tree_items.clear();
-        
-        let mut sorted_clients: Vec<_> = client_map.keys().collect();
-        sorted_clients.sort();
-        
-        for client in sorted_clients {
-            self.tree_items.push(TreeItem {
-                label: format!("📁 {}", client),
-                client: client.clone(),
-                account: String::new(),
-                role: String::new(),
-                level: 0,
-            });
-            
-            if *self.expanded.get(client).unwrap_or(&false) {
-                let accounts = &client_map[client];
-                let mut sorted_accounts: Vec<_> = accounts.keys().collect();
-                sorted_accounts.sort();
-                
-                for account in sorted_accounts {
-                    let account_key = format!("{}-{}", client, account);
-                    self.tree_items.push(TreeItem {
-                        label: format!("  📁 {}", account),
-                        client: client.clone(),
-                        account: account.clone(),
-                        role: String::new(),
-                        level: 1,
-                    });
-                    
-                    if *self.expanded.get(&account_key).unwrap_or(&false) {
-                        let roles = &accounts[account];
-                        let mut sorted_roles = roles.clone();
-                        sorted_roles.sort();
-                        
-                        for role in sorted_roles {
-                            self.tree_items.push(TreeItem {
-                                label: format!("    📄 {}", role),
-                                client: client.clone(),
-                                account: account.clone(),
-                                role: role.clone(),
-                                level: 2,
-                            });
-                        }
-                    }
+
+    fn rebuild_tree(&mut self) {
+        let mut items = Vec::new();
+        self.push_children(&[], &mut items);
+        self.tree_items = items;
+    }
+
+    /// Adds the items one level below `prefix`, recursing into expanded folders.
+    fn push_children(&self, prefix: &[String], items: &mut Vec<TreeItem>) {
+        let depth = prefix.len();
+        let below: Vec<(&Profile, &Vec<String>)> = self
+            .profiles
+            .iter()
+            .zip(&self.display_paths)
+            .filter(|(_, path)| path.len() > depth && path.starts_with(prefix))
+            .collect();
+        let segments: BTreeSet<&String> = below.iter().map(|(_, path)| &path[depth]).collect();
+        let indent = "  ".repeat(depth);
+
+        for segment in segments {
+            let mut path = prefix.to_vec();
+            path.push(segment.clone());
+
+            for (leaf, _) in below.iter().filter(|(_, p)| **p == path) {
+                items.push(TreeItem {
+                    label: leaf_label(&indent, segment, leaf),
+                    path: path.clone(),
+                    profile: Some(leaf.name.clone()),
+                });
+            }
+
+            if below.iter().any(|(_, p)| p.len() > path.len() && p.starts_with(&path)) {
+                items.push(TreeItem {
+                    label: format!("{}📁 {}", indent, segment),
+                    path: path.clone(),
+                    profile: None,
+                });
+                if self.expanded.contains(&path) {
+                    self.push_children(&path, items);
                 }
             }
         }
@@ -129,54 +120,58 @@ impl TreeApp {
         self.list_state.select(Some(i));
     }
 
+    /// Profile under the cursor, if it's on a leaf.
+    fn highlighted_profile(&self) -> Option<&Profile> {
+        let name = self.tree_items.get(self.list_state.selected()?)?.profile.as_ref()?;
+        self.profiles.iter().find(|p| &p.name == name)
+    }
+
     fn select(&mut self) {
         if let Some(i) = self.list_state.selected() {
-            let item = &self.tree_items[i].clone();
-            
-            if item.level == 2 && !item.role.is_empty() {
-                self.selected_profile = Some((item.client.clone(), item.account.clone(), item.role.clone()));
-            } else if item.level == 0 {
-                // Toggle client expansion
-                let expanded = !self.expanded.get(&item.client).unwrap_or(&false);
-                self.expanded.insert(item.client.clone(), expanded);
-                self.rebuild_from_profiles();
-            } else if item.level == 1 {
-                // Toggle account expansion
-                let account_key = format!("{}-{}", item.client, item.account);
-                let expanded = !self.expanded.get(&account_key).unwrap_or(&false);
-                self.expanded.insert(account_key, expanded);
-                self.rebuild_from_profiles();
+            let item = self.tree_items[i].clone();
+
+            if let Some(profile) = item.profile {
+                self.selected_profile = Some(profile);
+            } else {
+                // Toggle folder expansion
+                if !self.expanded.remove(&item.path) {
+                    self.expanded.insert(item.path);
+                }
+                self.rebuild_tree();
             }
         }
     }
-    
-    fn rebuild_from_profiles(&mut self) {
-        let mut client_map: HashMap<String, HashMap<String, Vec<String>>> = HashMap::new();
-        
-        for profile in &self.profiles {
-            client_map
-                .entry(profile.client.clone())
-                .or_default()
-                .entry(profile.account.clone())
-                .or_default()
-                .push(profile.role.clone());
-        }
-        
-        self.rebuild_tree(&client_map);
+}
+
+fn leaf_label(indent: &str, segment: &str, profile: &Profile) -> String {
+    let mut label = format!("{}📄 {}{}", indent, segment, chain_label(profile));
+    let annotation = profile.annotation();
+    if !annotation.is_empty() {
+        label.push_str(&format!("  {}", annotation));
+    }
+    label
+}
+
+/// Shows how a chained profile gets its credentials, e.g. ` ⇐ client-dev-admin`.
+fn chain_label(profile: &Profile) -> String {
+    let mut sources: Vec<&str> = profile.chain.iter().map(String::as_str).collect();
+    if let Some(credential_source) = &profile.credential_source {
+        sources.push(credential_source);
     }
+    sources.iter().map(|source| format!(" ⇐ {}", source)).collect()
 }
 
 fn ui(f: &mut Frame, app: &mut TreeApp) {
     let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(100)].as_ref())
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)].as_ref())
         .split(f.area());
 
     let items: Vec<ListItem> = app
         .tree_items
         .iter()
         .map(|item| {
-            let style = if item.level == 2 {
+            let style = if item.profile.is_some() {
                 Style::default().fg(Color::Green)
             } else {
                 Style::default().fg(Color::Yellow)
@@ -191,16 +186,39 @@ fn ui(f: &mut Frame, app: &mut TreeApp) {
         .highlight_symbol(">> ");
 
     f.render_stateful_widget(list, chunks[0], &mut app.list_state);
+
+    let details: Vec<Line> = app
+        .highlighted_profile()
+        .map(|profile| {
+            profile
+                .details()
+                .into_iter()
+                .map(|(label, value)| {
+                    Line::from(vec![
+                        Span::styled(format!("{}: ", label), Style::default().fg(Color::Cyan)),
+                        Span::raw(value),
+                    ])
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let details = Paragraph::new(details)
+        .block(Block::default().borders(Borders::ALL).title("Details"))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(details, chunks[1]);
 }
 
-pub fn tui_tree_select(profiles: &[Profile]) -> Result<Option<(String, String, String)>, Box<dyn std::error::Error>> {
+/// Returns the name of the selected profile, or `None` if the user quit.
+pub fn tui_tree_select(profiles: &[Profile], aliases: &Aliases) -> Result<Option<String>, Box<dyn std::error::Error>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = TreeApp::new(profiles.to_vec());
+    let mut app = TreeApp::new(profiles.to_vec(), aliases);
 
     loop {
         terminal.draw(|f| ui(f, &mut app))?;
@@ -230,4 +248,100 @@ pub fn tui_tree_select(profiles: &[Profile]) -> Result<Option<(String, String, S
     terminal.show_cursor()?;
 
     Ok(app.selected_profile)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, path: &[&str]) -> Profile {
+        Profile {
+            name: name.to_string(),
+            path: path.iter().map(|segment| segment.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn labels(app: &TreeApp) -> Vec<&str> {
+        app.tree_items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_tree_expands_any_depth() {
+        let mut chained = profile("acme-platform-prod-deploy", &["acme", "platform", "prod", "deploy"]);
+        chained.tags = vec!["prod".to_string()];
+        chained.role_arn = Some("arn:aws:iam::987654321098:role/Deploy".to_string());
+        chained.chain = vec!["acme-platform-prod-admin".to_string()];
+
+        let mut app = TreeApp::new(
+            vec![
+                profile("acme-platform-prod-admin", &["acme", "platform", "prod", "admin"]),
+                chained,
+                profile("odd", &["other", "odd"]),
+            ],
+            &Aliases::default(),
+        );
+        assert_eq!(labels(&app), vec!["📁 acme", "📁 other"]);
+
+        for _ in 0..3 {
+            app.select();
+            app.next();
+        }
+        assert_eq!(
+            labels(&app),
+            vec![
+                "📁 acme",
+                "  📁 platform",
+                "    📁 prod",
+                "      📄 admin",
+                "      📄 deploy ⇐ acme-platform-prod-admin  #prod",
+                "📁 other",
+            ]
+        );
+
+        app.next();
+        assert_eq!(
+            app.highlighted_profile().map(|p| p.name.as_str()),
+            Some("acme-platform-prod-deploy")
+        );
+        app.select();
+        assert_eq!(app.selected_profile.as_deref(), Some("acme-platform-prod-deploy"));
+
+        // Collapsing a folder hides everything below it
+        app.list_state.select(Some(0));
+        app.select();
+        assert_eq!(labels(&app), vec!["📁 acme", "📁 other"]);
+    }
+
+    #[test]
+    fn test_tree_uses_aliases() {
+        let naming = crate::profile::Naming::default();
+        let table = std::collections::HashMap::from([(
+            "account".to_string(),
+            std::collections::HashMap::from([(
+                "acmeplatformsharedservices".to_string(),
+                "shared-services".to_string(),
+            )]),
+        )]);
+        let aliases = Aliases::from_settings(&naming, Some(&table)).unwrap();
+
+        let mut app = TreeApp::new(
+            vec![profile(
+                "acme-acmeplatformsharedservices-admin",
+                &["acme", "acmeplatformsharedservices", "admin"],
+            )],
+            &aliases,
+        );
+        app.select();
+        assert_eq!(labels(&app), vec!["📁 acme", "  📁 shared-services"]);
+
+        app.next();
+        app.select();
+        app.next();
+        app.select();
+        assert_eq!(
+            app.selected_profile.as_deref(),
+            Some("acme-acmeplatformsharedservices-admin")
+        );
+    }
+}
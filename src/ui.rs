@@ -2,12 +2,21 @@ use skim::prelude::*;
 use std::io::Cursor;
 
 pub fn skim_pick(prompt: &str, options: Vec<String>) -> Option<String> {
+    run_skim(prompt, options, false).into_iter().next()
+}
+
+/// Like `skim_pick`, but Tab marks several options. Empty if aborted.
+pub fn skim_pick_many(prompt: &str, options: Vec<String>) -> Vec<String> {
+    run_skim(prompt, options, true)
+}
+
+fn run_skim(prompt: &str, options: Vec<String>, multi: bool) -> Vec<String> {
     let input = options.join("\n");
     let prompt_str = format!("{}> ", prompt);
     let options = SkimOptionsBuilder::default()
         .height(String::from("30%"))
         .prompt(prompt_str)
-        .multi(false)
+        .multi(multi)
         .bind(vec!["esc:abort".to_string()])
         .no_mouse(true)
         .build()
@@ -15,8 +24,15 @@ pub fn skim_pick(prompt: &str, options: Vec<String>) -> Option<String> {
 
     let item_reader = SkimItemReader::default();
     let items = item_reader.of_bufread(Cursor::new(input));
-    let output = Skim::run_with(&options, Some(items))?;
-
-    (!output.is_abort && !output.selected_items.is_empty())
-        .then(|| output.selected_items[0].output().to_string())
+    let Some(output) = Skim::run_with(&options, Some(items)) else {
+        return Vec::new();
+    };
+    if output.is_abort {
+        return Vec::new();
+    }
+    output
+        .selected_items
+        .iter()
+        .map(|item| item.output().to_string())
+        .collect()
 }
@@ -22,4 +22,581 @@ fn test_version_flag() {
     assert!(output.status.success());
     let stdout = String::from_utf8(output.stdout).unwrap();
     assert!(stdout.contains("0.1.0"));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_lint_reports_errors() {
+    // A HOME and directory of its own, so no settings or config.d files get in
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("config");
+    std::fs::write(
+        &config,
+        "[sso-session example]\nsso_start_url = https://example.com\n\n\
+         [profile client-dev-admin]\nsso_session = example\nsso_role_name = Admin\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .arg("--aws-config-path")
+        .arg(&config)
+        .arg("lint")
+        .env("HOME", home.path())
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("error: [profile client-dev-admin] missing sso_account_id"));
+}
+
+#[test]
+fn test_aws_config_file_env() {
+    // A HOME of its own, so the user's settings and ~/.aws/config don't count
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("custom-config");
+    std::fs::write(
+        &config,
+        "[sso-session example]\nsso_start_url = https://example.com\n\n\
+         [profile client-dev-admin]\nsso_session = example\n\
+         sso_account_id = 123456789012\nsso_role_name = Admin\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["auth", "--list"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("client-dev-admin"));
+}
+
+/// Answers a single HTTP request with `body` and returns the request line.
+fn serve_once(body: &'static str) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        write!(
+            reader.into_inner(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        request_line
+    });
+    (base_url, handle)
+}
+
+/// Writes an AWS config with one SSO profile under `home`, plus a cached SSO
+/// token that expires at `token_expires_at` when given. Returns the config path.
+fn sso_home(home: &std::path::Path, token_expires_at: Option<&str>) -> std::path::PathBuf {
+    let config = home.join("config");
+    std::fs::write(
+        &config,
+        "[sso-session example]\nsso_start_url = https://example.com\nsso_region = us-east-1\n\n\
+         [profile client-dev-admin]\nsso_session = example\n\
+         sso_account_id = 123456789012\nsso_role_name = Admin\nregion = eu-west-1\n",
+    )
+    .unwrap();
+    if let Some(expires_at) = token_expires_at {
+        // Named by sha1("example"), the sso-session name
+        let cache = home.join(".aws/sso/cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(
+            cache.join("c3499c2729730a7f807efb8676a92dcb6f8a3f8f.json"),
+            format!(
+                r#"{{"startUrl":"https://example.com","region":"us-east-1","accessToken":"token","expiresAt":"{}"}}"#,
+                expires_at
+            ),
+        )
+        .unwrap();
+    }
+    config
+}
+
+const ROLE_CREDENTIALS: &str = r#"{"roleCredentials":{"accessKeyId":"AKIA","secretAccessKey":"secret","sessionToken":"session","expiration":4070908800000}}"#;
+
+#[test]
+fn test_env_prints_role_credentials() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let (portal_url, server) = serve_once(ROLE_CREDENTIALS);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["env", "--native", "--format", "fish", "--client", "client", "--account", "dev", "--role", "admin"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .env("AWS_ENDPOINT_URL_SSO", &portal_url)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "set -gx AWS_ACCESS_KEY_ID 'AKIA'\n\
+         set -gx AWS_SECRET_ACCESS_KEY 'secret'\n\
+         set -gx AWS_SESSION_TOKEN 'session'\n\
+         set -gx AWS_CREDENTIAL_EXPIRATION '2099-01-01T00:00:00Z'\n\
+         set -gx AWS_REGION 'eu-west-1'\n\
+         set -gx AWS_DEFAULT_REGION 'eu-west-1'\n"
+    );
+    assert!(server.join().unwrap().starts_with("GET /federation/credentials?account_id=123456789012&role_name=Admin"));
+}
+
+#[test]
+fn test_env_requires_valid_session() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), None);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["env", "--client", "client", "--account", "dev", "--role", "admin"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No valid SSO session for profile client-dev-admin"));
+}
+
+#[test]
+fn test_credential_process_caches_credentials() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let (portal_url, server) = serve_once(ROLE_CREDENTIALS);
+    let expected = r#"{"Version":1,"AccessKeyId":"AKIA","SecretAccessKey":"secret","SessionToken":"session","Expiration":"2099-01-01T00:00:00Z"}"#;
+
+    // The second call is answered from the cache, the mock portal only serves one
+    for _ in 0..2 {
+        let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+            .args(["credential-process", "--profile", "client-dev-admin", "--native"])
+            .env("HOME", home.path())
+            .env("AWS_CONFIG_FILE", &config)
+            .env("AWS_ENDPOINT_URL_SSO", &portal_url)
+            .output()
+            .expect("Failed to execute command");
+
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), expected);
+    }
+    server.join().unwrap();
+}
+
+#[test]
+fn test_credential_process_fails_on_expired_token() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2020-01-01T00:00:00Z"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["credential-process", "--profile", "client-dev-admin", "--native"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No valid SSO session for profile client-dev-admin"));
+}
+
+#[test]
+fn test_shell_init_exports_profile() {
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("config");
+    // A credential_source profile needs no login
+    std::fs::write(
+        &config,
+        "[profile client-dev-admin]\nrole_arn = arn:aws:iam::123456789012:role/Admin\n\
+         credential_source = Environment\nregion = eu-west-1\n",
+    )
+    .unwrap();
+
+    let binary = std::path::Path::new(env!("CARGO_BIN_EXE_aws-sso-navigator"));
+    let path = format!(
+        "{}:{}",
+        binary.parent().unwrap().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let output = Command::new("bash")
+        .arg("-c")
+        .arg(
+            "eval \"$(aws-sso-navigator shell-init bash)\" && \
+             awsnav --client client --account dev --role admin >/dev/null && \
+             echo \"$AWS_PROFILE $AWS_REGION\"",
+        )
+        .env("HOME", home.path())
+        .env("PATH", path)
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute bash");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "client-dev-admin eu-west-1\n");
+}
+
+/// A PATH with a stub `aws` that logs its arguments to `aws.log` in `dir`,
+/// followed by the real PATH. `sso login` caches a token for the `example`
+/// sso-session of `sso_home`, and the list commands print one account and role.
+#[cfg(unix)]
+fn stub_aws_path(dir: &std::path::Path) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let stub = dir.join("aws");
+    std::fs::write(
+        &stub,
+        format!(
+            r#"#!/bin/sh
+echo "$@" >> {log}
+case "$1 $2" in
+"sso login")
+    echo "Attempting to automatically open the SSO authorization page"
+    mkdir -p "$HOME/.aws/sso/cache"
+    echo '{{"startUrl":"https://example.com","region":"us-east-1","accessToken":"token","expiresAt":"2099-01-01T00:00:00Z"}}' \
+        > "$HOME/.aws/sso/cache/c3499c2729730a7f807efb8676a92dcb6f8a3f8f.json" ;;
+"sso list-accounts")
+    echo '{{"accountList":[{{"accountId":"210987654321","accountName":"Staging"}}]}}' ;;
+"sso list-account-roles")
+    echo '{{"roleList":[{{"accountId":"210987654321","roleName":"ReadOnly"}}]}}' ;;
+esac
+"#,
+            log = dir.join("aws.log").display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+    format!("{}:{}", dir.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_runs_command_with_profile() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), None);
+    let path = stub_aws_path(home.path());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["exec", "--client", "client", "--account", "dev", "--role", "admin", "--"])
+        .args(["sh", "-c", "echo \"$AWS_PROFILE $AWS_REGION $AWS_SSO_NAVIGATOR_PROFILE\"; exit 3"])
+        .env("HOME", home.path())
+        .env("PATH", path)
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert_eq!(output.status.code(), Some(3));
+    // Only the command's output, the login messages go to stderr
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "client-dev-admin eu-west-1 client-dev-admin\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Attempting to automatically open the SSO authorization page"), "{}", stderr);
+    assert!(stderr.contains("Logged in, token valid until 2099-01-01T00:00:00Z"), "{}", stderr);
+    let aws_log = std::fs::read_to_string(home.path().join("aws.log")).unwrap();
+    assert_eq!(aws_log, "sso login --sso-session example\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_replaces_the_navigator() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let exec = |script: &str| {
+        Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+            .args(["exec", "--client", "client", "--account", "dev", "--role", "admin", "--", "sh", "-c", script])
+            .env("HOME", home.path())
+            .env("AWS_CONFIG_FILE", &config)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("Failed to execute command")
+    };
+
+    // The command runs in the navigator's process rather than as its child
+    let child = exec("echo $$");
+    let pid = child.id();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), pid.to_string());
+
+    // So a command killed by a signal is reported as such
+    let output = exec("kill -TERM $$").wait_with_output().unwrap();
+    assert_eq!(output.status.signal(), Some(15));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_auth_logs_in_once() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2020-01-01T00:00:00Z"));
+    let path = stub_aws_path(home.path());
+    let auth = || {
+        Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+            .args(["auth", "--client", "client", "--account", "dev", "--role", "admin"])
+            .env("HOME", home.path())
+            .env("PATH", &path)
+            .env("AWS_CONFIG_FILE", &config)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    // The expired token has no refresh token, so this logs in
+    let output = auth();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Logged in, token valid until 2099-01-01T00:00:00Z"), "{}", stderr);
+
+    // And the token it cached is reused
+    let output = auth();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("already has a valid session"));
+    let aws_log = std::fs::read_to_string(home.path().join("aws.log")).unwrap();
+    assert_eq!(aws_log, "sso login --sso-session example\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_import_adds_profiles() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), None);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["import", "example"])
+        .env("HOME", home.path())
+        .env("PATH", stub_aws_path(home.path()))
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let contents = std::fs::read_to_string(&config).unwrap();
+    assert!(contents.contains(
+        "[profile example-Staging-ReadOnly]\nsso_session = example\nsso_account_id = 210987654321\n"
+    ), "{}", contents);
+    let aws_log = std::fs::read_to_string(home.path().join("aws.log")).unwrap();
+    assert_eq!(
+        aws_log,
+        "sso login --sso-session example\n\
+         sso list-accounts --region us-east-1 --access-token token\n\
+         sso list-account-roles --region us-east-1 --access-token token --account-id 210987654321\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_spawns_shell() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let shell = home.path().join("shell");
+    std::fs::write(&shell, "#!/bin/sh\necho \"marker=$AWS_SSO_NAVIGATOR_PROFILE\"\n").unwrap();
+    std::fs::set_permissions(&shell, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["exec", "--client", "client", "--account", "dev", "--role", "admin"])
+        .env("HOME", home.path())
+        .env("PATH", stub_aws_path(home.path()))
+        .env("SHELL", &shell)
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "marker=client-dev-admin\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("already has a valid session"));
+    // The cached token was valid, so no login
+    assert!(!home.path().join("aws.log").exists());
+}
+
+#[test]
+fn test_logout_removes_caches_and_default() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let contents = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(&config, format!("[default]\nsso_session = example\n\n{}", contents)).unwrap();
+    // sha1("client-dev-admin"), as written by credential-process
+    let credentials = home.path().join(".config/aws-sso-navigator/credentials");
+    std::fs::create_dir_all(&credentials).unwrap();
+    let credentials = credentials.join("bcac3ba11a3f42f7733fa9765aae93cef2f7468d.json");
+    std::fs::write(&credentials, "{}").unwrap();
+
+    // `aws sso logout` would sign out of every session, not just the named one
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["logout", "example", "--aws-cli"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--all"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["logout", "example", "--clear-default"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Logged out of example (2 cached files removed)"), "{}", stdout);
+    assert!(stdout.contains("Removed the [default] profile"));
+    assert_eq!(std::fs::read_dir(home.path().join(".aws/sso/cache")).unwrap().count(), 0);
+    assert!(!credentials.exists());
+    assert!(!std::fs::read_to_string(&config).unwrap().contains("[default]"));
+}
+
+#[test]
+fn test_status_json() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["status", "--json"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let statuses: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(statuses[0]["name"], "example");
+    assert_eq!(statuses[0]["token"], "valid");
+    assert_eq!(statuses[0]["expires_at"], "2099-01-01T00:00:00Z");
+    assert_eq!(statuses[0]["profiles"], 1);
+}
+
+#[test]
+fn test_backup_list_and_restore() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), None);
+    let original = format!(
+        "# Hand-written\n[default]\nsso_session = example\n\n{}",
+        std::fs::read_to_string(&config).unwrap()
+    );
+    std::fs::write(&config, &original).unwrap();
+    let navigator = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+            .args(args)
+            .env("HOME", home.path())
+            .env("AWS_CONFIG_FILE", &config)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    let output = navigator(&["backup", "list"]);
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("No backups in "));
+
+    // Clearing the default changes the config, so it's backed up first
+    assert!(navigator(&["logout", "example", "--clear-default"]).status.success());
+    assert!(!std::fs::read_to_string(&config).unwrap().contains("[default]"));
+
+    let output = navigator(&["backup", "list"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert!(lines.next().unwrap().starts_with("ID"));
+    let row = lines.next().unwrap();
+    let columns: Vec<&str> = row.split_whitespace().collect();
+    assert_eq!(columns[3..], ["1", std::fs::canonicalize(&config).unwrap().to_str().unwrap()], "{}", row);
+    let id = columns[0];
+
+    // The backup is of another file than this AWS config
+    let other_config = home.path().join("other-config");
+    std::fs::write(&other_config, "").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["backup", "restore", id, "--yes"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &other_config)
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("pass --force"));
+    assert_eq!(std::fs::read_to_string(&other_config).unwrap(), "");
+
+    // Without a terminal to confirm on, it needs --yes
+    let output = navigator(&["backup", "restore", id]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("+[default]\n+sso_session = example\n"), "{}", stdout);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("pass --yes"));
+
+    let output = navigator(&["backup", "restore", id, "--yes"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), original);
+    // The config the restore replaced was backed up as well
+    let output = navigator(&["backup", "list"]);
+    assert_eq!(String::from_utf8(output.stdout).unwrap().lines().count(), 3);
+}
+
+#[test]
+fn test_level_flags_need_a_matching_level() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    let settings = home.path().join(".config/aws-sso-navigator");
+    std::fs::create_dir_all(&settings).unwrap();
+    std::fs::write(settings.join("config.toml"), "[naming]\nlevels = [\"team\", \"env\", \"role\"]\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+        .args(["auth", "--client", "client", "--role", "admin"])
+        .env("HOME", home.path())
+        .env("AWS_CONFIG_FILE", &config)
+        .output()
+        .expect("Failed to execute command");
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("--client doesn't match a naming level, use --select LEVEL=VALUE with one of: team, env, role"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_print_profile_only_after_login() {
+    let home = tempfile::tempdir().unwrap();
+    let config = sso_home(home.path(), Some("2099-01-01T00:00:00Z"));
+    // A browser that opens nothing
+    let settings = home.path().join(".config/aws-sso-navigator");
+    std::fs::create_dir_all(&settings).unwrap();
+    std::fs::write(settings.join("config.toml"), "browser = \"true\"\n").unwrap();
+    let profile_file = home.path().join("profile");
+    let auth = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_aws-sso-navigator"))
+            .args(["auth", "--client", "client", "--account", "dev", "--role", "admin", "--print-profile"])
+            .arg(&profile_file)
+            .args(extra)
+            .env("HOME", home.path())
+            .env("AWS_CONFIG_FILE", &config)
+            .output()
+            .expect("Failed to execute command")
+    };
+
+    // Opening the console doesn't log the shell in
+    let output = auth(&["--console"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(!profile_file.exists());
+
+    let output = auth(&[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read_to_string(&profile_file).unwrap(), "client-dev-admin\neu-west-1\n");
+}